@@ -0,0 +1,40 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use image::{Rgba, RgbaImage};
+use imoyo::background::Background;
+use imoyo::crop::{Crop, Padding};
+
+/// White 4000x3000 image with a block spanning `xs` and `ys`
+fn product_image(xs: std::ops::Range<u32>, ys: std::ops::Range<u32>) -> RgbaImage {
+    RgbaImage::from_fn(4000, 3000, |x, y| {
+        if xs.contains(&x) && ys.contains(&y) {
+            Rgba([90, 60, 30, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    })
+}
+
+fn detect(c: &mut Criterion) {
+    let crop = Crop::new(Padding::default(), Background::white(), None);
+    let images = [
+        ("product", product_image(1000..3000, 600..2400)),
+        ("small", product_image(1900..2100, 1400..1600)),
+    ];
+
+    let mut group = c.benchmark_group("detect");
+    group.sample_size(10);
+
+    for (name, image) in &images {
+        group.bench_function(format!("{name}/full_scan"), |b| {
+            b.iter(|| crop.detect_full_scan(black_box(image)))
+        });
+        group.bench_function(format!("{name}/proxy"), |b| {
+            b.iter(|| crop.detect(black_box(image)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, detect);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use image::{Pixel, Rgb, RgbImage, Rgba, RgbaImage};
+use imoyo::background::{self, Background};
+use imoyo::crop::{Crop, Padding};
+
+/// 3000x2000 image with a soft edged object, half of the pixels partly transparent
+fn source_image() -> RgbaImage {
+    RgbaImage::from_fn(3000, 2000, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, 90, ((x + y) % 256) as u8])
+    })
+}
+
+/// Previous per-pixel implementations, kept to compare against
+mod per_pixel {
+    use super::*;
+
+    pub fn filter_alpha(image: &RgbaImage, alpha_filter: u8) -> RgbaImage {
+        let (width, height) = image.dimensions();
+        let mut new_image = RgbaImage::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = image.get_pixel(x, y);
+
+                new_image.put_pixel(
+                    x,
+                    y,
+                    if pixel.channels()[3] < alpha_filter {
+                        Rgba([0; 4])
+                    } else {
+                        *pixel
+                    },
+                );
+            }
+        }
+
+        new_image
+    }
+
+    pub fn set_background(image: &RgbaImage, color: Rgba<u8>) -> RgbImage {
+        let (width, height) = image.dimensions();
+        let mut new_image = RgbImage::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = image.get_pixel(x, y).channels();
+                let alpha = pixel[3] as f32 / 255.0;
+                let blend =
+                    |c: usize| (color[c] as f32 * (1.0 - alpha) + pixel[c] as f32 * alpha) as u8;
+
+                new_image.put_pixel(x, y, Rgb([blend(0), blend(1), blend(2)]));
+            }
+        }
+
+        new_image
+    }
+
+    pub fn fill_to_square(image: &RgbaImage, color: Rgba<u8>) -> RgbaImage {
+        let (width, height) = image.dimensions();
+        let side = width.max(height);
+        let mut square_image = RgbaImage::new(side, side);
+        let padding_y = ((side - height) as f32 / 2.0).ceil() as u32;
+        let padding_x = ((side - width) as f32 / 2.0).ceil() as u32;
+
+        for (x, y, pixel) in square_image.enumerate_pixels_mut() {
+            if x < padding_x || x >= side - padding_x || y < padding_y || y >= side - padding_y {
+                *pixel = color;
+            } else {
+                *pixel = *image.get_pixel(x - padding_x, y - padding_y);
+            }
+        }
+
+        square_image.clone()
+    }
+}
+
+fn pixels(c: &mut Criterion) {
+    let image = source_image();
+    let bg = Background::white();
+    let crop = Crop::new(Padding::default(), Background::white(), None);
+
+    let mut group = c.benchmark_group("pixels");
+    group.sample_size(10);
+
+    group.bench_function("filter_alpha/per_pixel", |b| {
+        b.iter(|| per_pixel::filter_alpha(black_box(&image), 128))
+    });
+    group.bench_function("filter_alpha/rows", |b| {
+        b.iter_batched(
+            || image.clone(),
+            |mut image| background::filter_alpha(&mut image, 128),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("set_background/per_pixel", |b| {
+        b.iter(|| per_pixel::set_background(black_box(&image), bg.color))
+    });
+    group.bench_function("set_background/rows", |b| {
+        b.iter(|| bg.set_background(black_box(&image), false))
+    });
+
+    group.bench_function("fill_to_square/per_pixel", |b| {
+        b.iter(|| per_pixel::fill_to_square(black_box(&image), bg.color))
+    });
+    group.bench_function("fill_to_square/rows", |b| {
+        b.iter_batched(
+            || image.clone(),
+            |image| crop.fill_to_square(image),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, pixels);
+criterion_main!(benches);
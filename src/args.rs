@@ -1,12 +1,41 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use image::{imageops::FilterType, Rgb};
 
+use crate::background::ChromaKey;
+use crate::color::IccMode;
+use crate::crop::{BorderTouch, CutPolicy, Length, Padding};
+use crate::download::DownloadSettings;
+use crate::exif::MetadataPolicy;
+use crate::image_reader::MEGABYTE;
+use crate::image_writer::OutputFormat;
+use crate::log::{Level, LogFormat};
+use crate::report::ReportFormat;
 use crate::scaler::Scaler;
+use crate::server::ServeSettings;
+use crate::shadow::ShadowMode;
+use crate::watch::WatchSettings;
+
+/// Letters of the short options without a value, several can be combined like `-cs`
+pub const SHORT_FLAGS: &str = "vqcs";
+
+/// Letters of the short options followed by a value
+pub const SHORT_PARAMETERS: &str = "pfwabe";
+
+/// Shortest `--settle` in seconds, the watch loop waits half of it between size checks
+const MIN_SETTLE: f32 = 0.1;
+
+/// Long options without a value
+pub const LONG_FLAGS: [&str; 6] = ["crop", "square", "verbose", "quiet", "linear", "force"];
 
 #[derive(Debug, Default)]
 pub struct EdgeDetectionSettings {
     pub in_use: bool,
+    pub auto: bool,
     pub low_threshold: f32,
     pub high_threshold: f32,
+    pub blur_sigma: Option<f32>,
 }
 
 #[derive(Debug)]
@@ -14,63 +43,256 @@ pub struct Args {
     pub crop: bool,
     pub square: bool,
     pub scaler: Option<Scaler>,
-    pub padding: u32,
+    pub padding: Padding,
     pub ignored: Vec<usize>,
     pub alpha_filter: Option<u8>,
     pub background: Option<Rgb<u8>>,
-    pub verbose: bool,
+    /// Chroma-key colour that is made transparent before any other step
+    pub key: Option<ChromaKey>,
+    /// Alpha threshold for object bounds, colour is ignored and pixels are kept as they are
+    pub alpha_bounds: Option<u8>,
+    pub shadow: Option<ShadowMode>,
+    /// Bottom margin of the object on the square canvas
+    pub baseline: Option<Length>,
+    /// Aspect ratio `width:height` to crop to without padding
+    pub crop_to: Option<(u32, u32)>,
+    pub cut: CutPolicy,
+    pub log_level: Level,
+    pub log_format: LogFormat,
     pub edge_detection: EdgeDetectionSettings,
+    pub debug_dir: Option<PathBuf>,
+    pub analyze: Option<ReportFormat>,
+    pub border_touch: Option<BorderTouch>,
+    pub icc_mode: IccMode,
+    pub metadata_policy: MetadataPolicy,
+    pub linear: bool,
+    /// Memory limit in bytes
+    pub max_memory: Option<u64>,
+    pub download: DownloadSettings,
+    /// File with image paths or links, `-` for stdin
+    pub input_list: Option<String>,
+    /// Image is read from stdin and written to stdout
+    pub pipe: bool,
+    pub format: OutputFormat,
+    /// Processes images again even when the cache has an up to date output
+    pub force: bool,
+    /// JSON file for per image timings, sizes and errors
+    pub report: Option<String>,
 }
 
 impl Args {
     pub fn get() -> Self {
-        let mut ignored: Vec<usize> = vec![0];
+        Self::parse(&std::env::args().collect::<Vec<String>>())
+    }
 
-        let verbose = std::env::args()
-            .skip(1)
-            .any(|arg| arg.starts_with('-') && arg.contains('v'));
+    /// Parses a command line, `args[0]` is the program name
+    pub fn parse(args: &[String]) -> Self {
+        let mut ignored: Vec<usize> = vec![0];
 
-        let crop = std::env::args()
-            .skip(1)
-            .any(|arg| arg.starts_with('-') && arg.contains('c'));
+        let pipe = args
+            .windows(2)
+            .any(|args| args[1] == "-" && args[0] != "--input-list");
 
-        let square = std::env::args()
+        let verbosity = args
+            .iter()
             .skip(1)
-            .any(|arg| arg.starts_with('-') && arg.contains('s'));
-
-        let padding = if crop {
-            Self::get_parameter("p", &mut ignored).map_or(0, |p| {
-                p.parse::<u32>()
-                    .unwrap_or_else(|err| panic!("Failed to parse padding: {err}"))
+            .map(|arg| match arg.as_str() {
+                "--verbose" => 1,
+                arg if Self::is_flag(arg, "v") => arg.matches('v').count(),
+                _ => 0,
             })
+            .sum::<usize>();
+
+        let log_level = if Self::is_switch(args, "q", "quiet") {
+            Level::Error
         } else {
-            0
+            match verbosity {
+                0 => Level::Info,
+                1 => Level::Verbose,
+                _ => Level::Debug,
+            }
         };
 
-        let upscaler = Self::read_scaler(&mut ignored);
+        let crop = Self::is_switch(args, "c", "crop");
+
+        let square = Self::is_switch(args, "s", "square");
 
-        let alpha_filter = Self::get_parameter("a", &mut ignored).map(|a| {
+        let padding = Self::get_long_parameter(args, "padding", &mut ignored).map_or(
+            Self::get_parameter(args, "p", &mut ignored).map_or(Padding::default(), |p| {
+                Padding::uniform(Length::Pixels(
+                    p.parse::<u32>()
+                        .unwrap_or_else(|err| panic!("Failed to parse padding: {err}")),
+                ))
+            }),
+            |p| Self::parse_padding(&p),
+        );
+
+        let linear = Self::is_long_flag(args, "linear");
+
+        let upscaler = Self::read_scaler(args, &mut ignored, linear);
+
+        let alpha_filter = Self::get_parameter(args, "a", &mut ignored).map(|a| {
             a.parse::<u8>()
                 .unwrap_or_else(|err| panic!("Failed to parse alpha filter: {err}"))
         });
 
-        let background = Self::get_parameter("b", &mut ignored).map(|b| {
-            let rgb = b
-                .split(',')
-                .map(|x| {
-                    x.trim()
-                        .parse::<u8>()
-                        .unwrap_or_else(|err| panic!("Failed to parse background: {err}"))
+        let background =
+            Self::get_parameter(args, "b", &mut ignored).map(|b| Self::parse_rgb(&b, "Background"));
+
+        let key_tolerance =
+            Self::get_long_parameter(args, "key-tolerance", &mut ignored).map(|t| {
+                match t.parse::<f32>() {
+                    Ok(tolerance) if tolerance > 0.0 => tolerance,
+                    Ok(_) => panic!("Key tolerance must be positive"),
+                    Err(err) => panic!("Failed to parse key tolerance: {err}"),
+                }
+            });
+
+        let key = Self::get_long_parameter(args, "key", &mut ignored).map(|k| {
+            ChromaKey::new(
+                Self::parse_rgb(&k, "Key colour"),
+                key_tolerance.unwrap_or(30.0) / 100.0,
+            )
+            .unwrap_or_else(|err| panic!("{err}"))
+        });
+
+        if key.is_none() && key_tolerance.is_some() {
+            panic!("--key-tolerance only applies to --key");
+        }
+
+        let alpha_bounds = Self::get_long_parameter(args, "alpha-bounds", &mut ignored).map(|a| {
+            a.parse::<u8>()
+                .unwrap_or_else(|err| panic!("Failed to parse alpha bounds: {err}"))
+        });
+
+        let shadow =
+            Self::get_long_parameter(args, "shadow", &mut ignored).map(|s| match s.as_str() {
+                "include" => ShadowMode::Include,
+                "exclude" => ShadowMode::Exclude,
+                "remove" => ShadowMode::Remove,
+                &_ => panic!("Unknown shadow mode: {s}"),
+            });
+
+        let baseline = Self::get_long_parameter(args, "baseline", &mut ignored).map(|b| {
+            if !square {
+                panic!("--baseline only applies to the square canvas of -s");
+            }
+
+            match Self::parse_length(&b) {
+                Length::Percent(percent) if percent >= 100.0 => {
+                    panic!("Baseline must be less than 100%")
+                }
+                length => length,
+            }
+        });
+
+        let crop_to = Self::get_long_parameter(args, "crop-to", &mut ignored).map(|c| {
+            if crop || square {
+                panic!("--crop-to can't be combined with -c or -s");
+            }
+
+            c.split_once(':')
+                .map(|(w, h)| (w.trim().parse::<u32>(), h.trim().parse::<u32>()))
+                .and_then(|ratio| match ratio {
+                    (Ok(w), Ok(h)) if w > 0 && h > 0 => Some((w, h)),
+                    _ => None,
                 })
-                .collect::<Vec<u8>>();
-            if rgb.len() != 3 {
-                panic!("Background must be r,g,b");
+                .unwrap_or_else(|| panic!("Crop ratio must be W:H, got {c}"))
+        });
+
+        let cut = Self::get_long_parameter(args, "cut", &mut ignored).map_or(
+            CutPolicy::Fail,
+            |c| match c.as_str() {
+                "warn" => CutPolicy::Warn,
+                "fail" => CutPolicy::Fail,
+                &_ => panic!("Unknown cut policy: {c}"),
+            },
+        );
+
+        let edge_detection = Self::get_edge_detection_settings(args, &mut ignored);
+
+        let debug_dir =
+            Self::get_long_parameter(args, "debug-dir", &mut ignored).map(PathBuf::from);
+
+        let analyze = Self::get_long_parameter(args, "analyze", &mut ignored).map(|format| {
+            match format.as_str() {
+                "json" => ReportFormat::Json,
+                "csv" => ReportFormat::Csv,
+                &_ => panic!("Unknown report format: {format}"),
+            }
+        });
+
+        let border_touch = Self::get_long_parameter(args, "border-touch", &mut ignored).map(|b| {
+            match b.as_str() {
+                "warn" => BorderTouch::Warn,
+                "fail" => BorderTouch::Fail,
+                "no-pad" => BorderTouch::NoPad,
+                &_ => panic!("Unknown border touch policy: {b}"),
             }
+        });
+
+        let icc_mode = Self::get_long_parameter(args, "icc", &mut ignored).map_or(
+            IccMode::Convert,
+            |i| match i.as_str() {
+                "convert" => IccMode::Convert,
+                "preserve" => IccMode::Preserve,
+                &_ => panic!("Unknown ICC mode: {i}"),
+            },
+        );
 
-            Rgb([rgb[0], rgb[1], rgb[2]])
+        let metadata_policy = Self::get_long_parameter(args, "strip", &mut ignored).map_or(
+            MetadataPolicy::StripAll,
+            |m| match m.as_str() {
+                "all" => MetadataPolicy::StripAll,
+                "keep-copyright" => MetadataPolicy::KeepCopyright,
+                "keep-all" => MetadataPolicy::KeepAll,
+                &_ => panic!("Unknown metadata policy: {m}"),
+            },
+        );
+
+        let max_memory = Self::get_long_parameter(args, "max-memory", &mut ignored).map(|m| {
+            m.parse::<u64>()
+                .unwrap_or_else(|err| panic!("Failed to parse max memory: {err}"))
+                * MEGABYTE
         });
 
-        let edge_detection = Self::get_edge_detection_settings(&mut ignored);
+        let download = Self::get_download_settings(args, &mut ignored);
+
+        let input_list = Self::get_long_parameter(args, "input-list", &mut ignored);
+
+        if pipe && input_list.as_deref() == Some("-") {
+            panic!("Stdin can't be used for both the image and --input-list");
+        }
+
+        let format = Self::get_long_parameter(args, "format", &mut ignored).map_or(
+            OutputFormat::Jpeg,
+            |f| match f.as_str() {
+                "jpeg" | "jpg" => OutputFormat::Jpeg,
+                "png" => OutputFormat::Png,
+                "webp" => OutputFormat::Webp,
+                &_ => panic!("Unknown output format: {f}"),
+            },
+        );
+
+        let force = Self::is_long_flag(args, "force");
+
+        let report = Self::get_long_parameter(args, "report", &mut ignored);
+
+        let log_format = Self::get_long_parameter(args, "log-format", &mut ignored).map_or(
+            LogFormat::Text,
+            |l| match l.as_str() {
+                "text" => LogFormat::Text,
+                "json" => LogFormat::Json,
+                &_ => panic!("Unknown log format: {l}"),
+            },
+        );
+
+        if let Some(unknown) = args.iter().enumerate().skip(1).find_map(|(i, arg)| {
+            arg.strip_prefix("--")
+                .filter(|name| !ignored.contains(&i) && !LONG_FLAGS.contains(name))
+        }) {
+            panic!("Unknown option --{unknown}");
+        }
 
         Self {
             crop,
@@ -80,62 +302,338 @@ impl Args {
             ignored,
             alpha_filter,
             background,
-            verbose,
+            key,
+            alpha_bounds,
+            shadow,
+            baseline,
+            crop_to,
+            cut,
+            log_level,
+            log_format,
             edge_detection,
+            debug_dir,
+            analyze,
+            border_touch,
+            icc_mode,
+            metadata_policy,
+            linear,
+            max_memory,
+            download,
+            input_list,
+            pipe,
+            format,
+            force,
+            report,
         }
     }
 
-    fn get_parameter(name: &str, ignored: &mut Vec<usize>) -> Option<String> {
-        std::env::args()
+    /// Every setting that changes the output image, outputs made with other settings are stale
+    pub fn settings_key(&self) -> String {
+        format!(
+            "{} {} {} {:?} {} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {} {:?}",
+            env!("CARGO_PKG_VERSION"),
+            self.crop,
+            self.square,
+            self.scaler,
+            self.padding,
+            self.alpha_filter,
+            self.background,
+            self.key,
+            self.alpha_bounds,
+            self.shadow,
+            self.baseline,
+            self.crop_to,
+            self.cut,
+            self.edge_detection,
+            self.border_touch,
+            self.icc_mode,
+            self.metadata_policy,
+            self.linear,
+            self.format,
+        )
+    }
+
+    /// Settings of `serve` and the remaining arguments used as pipeline defaults
+    pub fn serve_settings(args: &[String]) -> (ServeSettings, Vec<String>) {
+        let mut ignored: Vec<usize> = vec![0];
+        let default = ServeSettings::default();
+
+        let settings = ServeSettings {
+            host: Self::get_long_parameter(args, "host", &mut ignored).unwrap_or(default.host),
+            port: Self::get_long_parameter(args, "port", &mut ignored).map_or(default.port, |p| {
+                p.parse::<u16>()
+                    .unwrap_or_else(|err| panic!("Failed to parse port: {err}"))
+            }),
+            max_body: Self::get_long_parameter(args, "max-body", &mut ignored).map_or(
+                default.max_body,
+                |m| {
+                    m.parse::<u64>()
+                        .unwrap_or_else(|err| panic!("Failed to parse max body size: {err}"))
+                        * MEGABYTE
+                },
+            ),
+            workers: Self::get_long_parameter(args, "workers", &mut ignored).map_or(
+                default.workers,
+                |w| {
+                    w.parse::<usize>()
+                        .unwrap_or_else(|err| panic!("Failed to parse workers: {err}"))
+                },
+            ),
+        };
+
+        let rest = args
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !ignored.contains(i))
+            .map(|(_, arg)| arg.clone())
+            .collect();
+
+        (settings, rest)
+    }
+
+    /// Settings of `watch <dir>` and the remaining arguments for the pipeline
+    pub fn watch_settings(args: &[String]) -> (WatchSettings, Vec<String>) {
+        // `watch` stays as the program name for `Args::parse`, the folder is taken
+        let mut ignored: Vec<usize> = vec![1];
+
+        let dir = PathBuf::from(
+            args.get(1)
+                .filter(|dir| !dir.starts_with('-'))
+                .unwrap_or_else(|| panic!("Missing folder to watch")),
+        );
+        let out_dir = Self::get_long_parameter(args, "out-dir", &mut ignored)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| panic!("Missing --out-dir"));
+
+        let settings = WatchSettings {
+            settle: Duration::from_secs_f32(
+                Self::get_long_parameter(args, "settle", &mut ignored).map_or(2.0, |s| {
+                    match s.parse::<f32>() {
+                        Ok(settle) if settle >= MIN_SETTLE => settle,
+                        Ok(_) => panic!("Settle time must be at least {MIN_SETTLE} seconds"),
+                        Err(err) => panic!("Failed to parse settle time: {err}"),
+                    }
+                }),
+            ),
+            log: Self::get_long_parameter(args, "log", &mut ignored)
+                .map_or(out_dir.join("imoyo-watch.log"), PathBuf::from),
+            dir,
+            out_dir,
+        };
+
+        let rest = args
+            .iter()
             .enumerate()
+            .filter(|(i, _)| !ignored.contains(i))
+            .map(|(_, arg)| arg.clone())
+            .collect();
+
+        (settings, rest)
+    }
+
+    fn is_flag(arg: &str, name: &str) -> bool {
+        arg.starts_with('-') && !arg.starts_with("--") && arg.contains(name)
+    }
+
+    /// A short flag letter or its long name
+    fn is_switch(args: &[String], short: &str, long: &str) -> bool {
+        args.iter().skip(1).any(|arg| Self::is_flag(arg, short)) || Self::is_long_flag(args, long)
+    }
+
+    fn is_long_flag(args: &[String], name: &str) -> bool {
+        args.iter()
             .skip(1)
-            .find(|(_, arg)| arg.starts_with('-') && arg.contains(name))
+            .any(|arg| arg.strip_prefix("--") == Some(name))
+    }
+
+    fn get_long_parameter(args: &[String], name: &str, ignored: &mut Vec<usize>) -> Option<String> {
+        args.iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, arg)| arg.strip_prefix("--") == Some(name))
             .map(|(i, _)| {
                 ignored.push(i);
                 ignored.push(i + 1);
 
-                std::env::args()
-                    .nth(i + 1)
-                    .expect("Missing parameter value")
+                args.get(i + 1)
+                    .cloned()
+                    .unwrap_or_else(|| panic!("Missing value for --{name}"))
             })
     }
 
-    fn read_scaler(ignored: &mut Vec<usize>) -> Option<Scaler> {
-        let width = Self::get_parameter("w", ignored).map(|w| {
+    fn get_parameter(args: &[String], name: &str, ignored: &mut Vec<usize>) -> Option<String> {
+        args.iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, arg)| Self::is_flag(arg, name))
+            .map(|(i, _)| {
+                ignored.push(i);
+                ignored.push(i + 1);
+
+                args.get(i + 1).cloned().expect("Missing parameter value")
+            })
+    }
+
+    /// One to four CSS style sides in pixels or percent, e.g. `10`, `5%,0` or `0,0,20%,0`
+    fn parse_padding(value: &str) -> Padding {
+        let sides = value
+            .split(',')
+            .map(Self::parse_length)
+            .collect::<Vec<Length>>();
+
+        match sides[..] {
+            [all] => Padding::uniform(all),
+            [vertical, horizontal] => Padding {
+                top: vertical,
+                right: horizontal,
+                bottom: vertical,
+                left: horizontal,
+            },
+            [top, horizontal, bottom] => Padding {
+                top,
+                right: horizontal,
+                bottom,
+                left: horizontal,
+            },
+            [top, right, bottom, left] => Padding {
+                top,
+                right,
+                bottom,
+                left,
+            },
+            _ => panic!("Padding must be top,right,bottom,left"),
+        }
+    }
+
+    /// `10`, `10px` or `10%`
+    fn parse_length(value: &str) -> Length {
+        let value = value.trim();
+
+        match value.strip_suffix('%') {
+            Some(percent) => Length::Percent(
+                percent
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|percent| *percent >= 0.0)
+                    .unwrap_or_else(|| panic!("Failed to parse length: {value}")),
+            ),
+            None => Length::Pixels(
+                value
+                    .strip_suffix("px")
+                    .unwrap_or(value)
+                    .parse::<u32>()
+                    .unwrap_or_else(|err| panic!("Failed to parse length {value}: {err}")),
+            ),
+        }
+    }
+
+    /// `r,g,b`, `name` is used in error messages
+    fn parse_rgb(value: &str, name: &str) -> Rgb<u8> {
+        let rgb = value
+            .split(',')
+            .map(|x| {
+                x.trim()
+                    .parse::<u8>()
+                    .unwrap_or_else(|err| panic!("Failed to parse {name}: {err}"))
+            })
+            .collect::<Vec<u8>>();
+        if rgb.len() != 3 {
+            panic!("{name} must be r,g,b");
+        }
+
+        Rgb([rgb[0], rgb[1], rgb[2]])
+    }
+
+    fn read_scaler(args: &[String], ignored: &mut Vec<usize>, linear: bool) -> Option<Scaler> {
+        let width = Self::get_parameter(args, "w", ignored).map(|w| {
             w.parse::<u32>()
                 .unwrap_or_else(|err| panic!("Failed to parse width: {err}"))
         })?;
 
         let filter_type: FilterType =
-            Self::get_parameter("f", ignored).map_or(FilterType::Lanczos3, |f| match f.as_str() {
-                "n" => FilterType::Nearest,
-                "t" => FilterType::Triangle,
-                "c" => FilterType::CatmullRom,
-                "g" => FilterType::Gaussian,
-                "l" => FilterType::Lanczos3,
-                &_ => panic!("Unknown filter type: {f}"),
+            Self::get_parameter(args, "f", ignored).map_or(FilterType::Lanczos3, |f| {
+                match f.as_str() {
+                    "n" => FilterType::Nearest,
+                    "t" => FilterType::Triangle,
+                    "c" => FilterType::CatmullRom,
+                    "g" => FilterType::Gaussian,
+                    "l" => FilterType::Lanczos3,
+                    &_ => panic!("Unknown filter type: {f}"),
+                }
             });
 
-        println!("Filter: {filter_type:?}");
+        Some(Scaler::new(filter_type, width, linear))
+    }
+
+    fn get_download_settings(args: &[String], ignored: &mut Vec<usize>) -> DownloadSettings {
+        let default = DownloadSettings::default();
 
-        Some(Scaler::new(filter_type, width))
+        DownloadSettings {
+            timeout: Self::get_long_parameter(args, "timeout", ignored).map_or(
+                default.timeout,
+                |t| {
+                    Duration::from_secs(
+                        t.parse::<u64>()
+                            .unwrap_or_else(|err| panic!("Failed to parse timeout: {err}")),
+                    )
+                },
+            ),
+            retries: Self::get_long_parameter(args, "retries", ignored).map_or(
+                default.retries,
+                |r| {
+                    r.parse::<u32>()
+                        .unwrap_or_else(|err| panic!("Failed to parse retries: {err}"))
+                },
+            ),
+            max_size: Self::get_long_parameter(args, "max-download", ignored).map_or(
+                default.max_size,
+                |m| {
+                    m.parse::<u64>()
+                        .unwrap_or_else(|err| panic!("Failed to parse max download size: {err}"))
+                        * MEGABYTE
+                },
+            ),
+        }
     }
 
-    fn get_edge_detection_settings(ignored: &mut Vec<usize>) -> EdgeDetectionSettings {
-        Self::get_parameter("e", ignored).map_or(EdgeDetectionSettings::default(), |e| {
-            let values = e.split(&[',', ';']).collect::<Vec<&str>>();
-            let low_threshold = values
-                .first()
-                .map_or(1.0, |v| v.trim().parse::<f32>().unwrap_or(1.0));
+    fn get_edge_detection_settings(
+        args: &[String],
+        ignored: &mut Vec<usize>,
+    ) -> EdgeDetectionSettings {
+        Self::get_parameter(args, "e", ignored).map_or(EdgeDetectionSettings::default(), |e| {
+            let values = e.split(&[',', ';']).map(str::trim).collect::<Vec<&str>>();
+            let auto = values.first().is_some_and(|v| *v == "auto");
+
+            let parse = |value: &str| {
+                value
+                    .parse::<f32>()
+                    .unwrap_or_else(|err| panic!("Failed to parse edge detection value: {err}"))
+            };
+
+            let (low_threshold, high_threshold, blur_sigma) = if auto {
+                (0.0, 0.0, values.get(1).map(|v| parse(v)))
+            } else {
+                (
+                    values.first().map_or(1.0, |v| parse(v)),
+                    values.get(1).map_or(10.0, |v| parse(v)),
+                    values.get(2).map(|v| parse(v)),
+                )
+            };
 
-            let high_threshold = values
-                .get(1)
-                .map_or(10.0, |v| v.trim().parse::<f32>().unwrap_or(10.0));
+            if low_threshold > high_threshold {
+                panic!("Low threshold must not exceed high threshold");
+            }
+
+            if blur_sigma.is_some_and(|sigma| sigma <= 0.0) {
+                panic!("Blur sigma must be positive");
+            }
 
             EdgeDetectionSettings {
                 in_use: true,
+                auto,
                 low_threshold,
                 high_threshold,
+                blur_sigma,
             }
         })
     }
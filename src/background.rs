@@ -1,11 +1,81 @@
-use image::{Rgb, RgbImage, Rgba, RgbaImage};
+use image::{Pixel, Rgb, Rgba};
+
+use crate::color::{from_ycbcr, linear_to_srgb, srgb_to_linear, to_ycbcr};
+use crate::depth::{self, Depth, RgbBuffer, RgbaBuffer};
 
 const WHITE: Rgba<u8> = Rgba([255, 255, 255, 0]);
 
+/// Width of the soft matte band as share of the key tolerance
+const KEY_SOFTNESS: f32 = 0.5;
+
+/// Pixels this close to the keyed background get their key colour cast removed
+const SPILL_RADIUS: u32 = 2;
+
+/// Luma floor for normalising chroma, keeps noise in near black pixels from looking saturated
+const MIN_LUMA: f32 = 0.1;
+
+/// Key colour of a chroma-key (green screen) background
+#[derive(Debug, Clone, Copy)]
+pub struct ChromaKey {
+    /// Unit direction of the key colour in the chroma plane
+    direction: [f32; 2],
+    /// Chroma of the key divided by its luma
+    magnitude: f32,
+    /// Chroma distance below which a pixel is background, as share of the key chroma
+    tolerance: f32,
+}
+
+impl ChromaKey {
+    pub fn new(color: Rgb<u8>, tolerance: f32) -> Result<Self, String> {
+        let [y, cb, cr] = to_ycbcr(color.0.map(|c| c as f32 / 255.0));
+        let chroma = cb.hypot(cr);
+
+        if chroma < 0.05 {
+            return Err(format!("Key colour {:?} is too close to grey", color.0));
+        }
+
+        Ok(Self {
+            direction: [cb / chroma, cr / chroma],
+            magnitude: chroma / y.max(MIN_LUMA),
+            tolerance,
+        })
+    }
+
+    /// Opacity by distance of the pixel chroma from the key relative to luma, so shadows on the key still match
+    fn matte(&self, rgb: [f32; 3]) -> f32 {
+        let [y, cb, cr] = to_ycbcr(rgb);
+        let y = y.max(MIN_LUMA);
+        let distance = (cb / y - self.direction[0] * self.magnitude)
+            .hypot(cr / y - self.direction[1] * self.magnitude)
+            / self.magnitude;
+
+        ((distance - self.tolerance) / (self.tolerance * KEY_SOFTNESS)).clamp(0.0, 1.0)
+    }
+
+    /// Removes the chroma component towards the key colour and keeps the luma
+    fn suppress_spill(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let [y, cb, cr] = to_ycbcr(rgb);
+        let spill = cb * self.direction[0] + cr * self.direction[1];
+
+        if spill <= 0.0 {
+            return rgb;
+        }
+
+        from_ycbcr([
+            y,
+            cb - spill * self.direction[0],
+            cr - spill * self.direction[1],
+        ])
+    }
+}
+
 #[derive(Debug)]
 pub struct Background {
     pub color: Rgba<u8>,
     delta: u8,
+    key: Option<ChromaKey>,
+    /// Only alpha decides, pixels less opaque than this are background whatever their colour
+    alpha_bounds: Option<u8>,
 }
 
 impl Background {
@@ -13,6 +83,8 @@ impl Background {
         Self {
             color: WHITE,
             delta: 3,
+            key: None,
+            alpha_bounds: None,
         }
     }
 
@@ -20,25 +92,56 @@ impl Background {
         Self {
             color: Rgba([rgb.0[0], rgb.0[1], rgb.0[2], 0]),
             delta: 3,
+            key: None,
+            alpha_bounds: None,
         }
     }
 
-    pub fn is_background(&self, pixel: Rgba<u8>) -> bool {
-        let rgb = pixel.0;
+    /// Detects a chroma-key background instead of a near white one
+    pub fn with_key(self, key: Option<ChromaKey>) -> Self {
+        Self { key, ..self }
+    }
+
+    /// Detects the object by alpha alone, see `alpha_bounds`
+    pub fn with_alpha_bounds(self, alpha_bounds: Option<u8>) -> Self {
+        Self {
+            alpha_bounds,
+            ..self
+        }
+    }
+
+    /// Background colour in the channel type of the image
+    pub fn pixel<T: Depth>(&self) -> T::Rgba {
+        depth::rgba(self.color.0.map(|c| T::from_unit(c as f32 / 255.0)))
+    }
+
+    pub fn is_background<T: Depth>(&self, pixel: T::Rgba) -> bool {
+        let rgb = pixel.channels();
+
+        if let Some(alpha_bounds) = self.alpha_bounds {
+            return rgb[3].as_f32() < alpha_bounds as f32 * T::max_f32() / 255.0;
+        }
 
-        if rgb[3] == 0 {
+        if rgb[3] == T::zero() {
             return true;
         }
 
-        rgb.iter().all(|x| x > &(u8::MAX - self.delta))
+        if let Some(key) = &self.key {
+            return key.matte([rgb[0].to_unit(), rgb[1].to_unit(), rgb[2].to_unit()]) == 0.0;
+        }
+
+        // Delta is given in 8 bit steps
+        let threshold = T::max_f32() - self.delta as f32 * T::max_f32() / 255.0;
+
+        rgb.iter().all(|x| x.as_f32() > threshold)
     }
 
-    pub fn is_row_neighbours_white(&self, image: &RgbaImage, x: u32, y: u32) -> bool {
+    pub fn is_row_neighbours_white<T: Depth>(&self, image: &RgbaBuffer<T>, x: u32, y: u32) -> bool {
         let trigger: u8 = 3;
         let mut count: u8 = 0;
 
         for i in x.saturating_sub(1)..=x.saturating_add(1) {
-            if i < image.width() && !self.is_background(*image.get_pixel(i, y)) {
+            if i < image.width() && !self.is_background::<T>(*image.get_pixel(i, y)) {
                 count += 1;
             }
 
@@ -50,12 +153,17 @@ impl Background {
         true
     }
 
-    pub fn is_column_neighbours_white(&self, image: &RgbaImage, x: u32, y: u32) -> bool {
+    pub fn is_column_neighbours_white<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        x: u32,
+        y: u32,
+    ) -> bool {
         let trigger: u8 = 3;
         let mut count: u8 = 0;
 
         for i in y.saturating_sub(1)..=y.saturating_add(1) {
-            if i < image.height() && !self.is_background(*image.get_pixel(x, i)) {
+            if i < image.height() && !self.is_background::<T>(*image.get_pixel(x, i)) {
                 count += 1;
             }
 
@@ -67,50 +175,120 @@ impl Background {
         true
     }
 
-    pub fn rgb_pixel_with_background(&self, pixel: Rgba<u8>) -> Rgb<u8> {
-        let alpha = pixel.0[3] as f32 / 255.0;
+    /// Blends every pixel with the background colour, in linear light when `linear` is set
+    pub fn set_background<T: Depth>(&self, image: &RgbaBuffer<T>, linear: bool) -> RgbBuffer<T> {
+        let (width, height) = image.dimensions();
+        let mut new_image = RgbBuffer::<T>::new(width, height);
+        let pixels = image.chunks_exact(4).zip(new_image.chunks_exact_mut(3));
+
+        if linear {
+            let color = self.color.0.map(|c| srgb_to_linear(c as f32 / 255.0));
 
-        Rgb([
-            (self.color.0[0] as f32 * (1.0 - alpha) + pixel.0[0] as f32 * alpha) as u8,
-            (self.color.0[1] as f32 * (1.0 - alpha) + pixel.0[1] as f32 * alpha) as u8,
-            (self.color.0[2] as f32 * (1.0 - alpha) + pixel.0[2] as f32 * alpha) as u8,
-        ])
+            for (source, target) in pixels {
+                let alpha = source[3].to_unit();
+
+                for c in 0..3 {
+                    let value = srgb_to_linear(source[c].to_unit());
+                    target[c] =
+                        T::from_unit(linear_to_srgb(color[c] * (1.0 - alpha) + value * alpha));
+                }
+            }
+        } else {
+            let color = self
+                .color
+                .0
+                .map(|c| T::from_unit(c as f32 / 255.0).as_f32());
+
+            for (source, target) in pixels {
+                let alpha = source[3].to_unit();
+
+                for c in 0..3 {
+                    target[c] = T::from_f32(color[c] * (1.0 - alpha) + source[c].as_f32() * alpha);
+                }
+            }
+        }
+
+        new_image
     }
 
-    pub fn set_background(&self, image: &RgbaImage) -> RgbImage {
+    /// Makes key coloured pixels transparent and removes the key colour cast from the object edges
+    pub fn remove_key<T: Depth>(&self, image: &mut RgbaBuffer<T>) {
+        let Some(key) = &self.key else {
+            return;
+        };
+
         let (width, height) = image.dimensions();
-        let mut new_image = RgbImage::new(width, height);
+        let mattes = image
+            .chunks_exact(4)
+            .map(|pixel| key.matte([pixel[0].to_unit(), pixel[1].to_unit(), pixel[2].to_unit()]))
+            .collect::<Vec<f32>>();
+
+        for (i, pixel) in image.chunks_exact_mut(4).enumerate() {
+            let matte = mattes[i];
 
-        for y in 0..height {
-            for x in 0..width {
-                new_image.put_pixel(x, y, self.rgb_pixel_with_background(*image.get_pixel(x, y)));
+            if matte == 0.0 {
+                pixel[3] = T::zero();
+                continue;
+            }
+
+            pixel[3] = T::from_unit(pixel[3].to_unit() * matte);
+
+            let (x, y) = ((i % width as usize) as u32, (i / width as usize) as u32);
+
+            if near_key(&mattes, (x, y), (width, height)) {
+                let rgb = key.suppress_spill([
+                    pixel[0].to_unit(),
+                    pixel[1].to_unit(),
+                    pixel[2].to_unit(),
+                ]);
+
+                for c in 0..3 {
+                    pixel[c] = T::from_unit(rgb[c]);
+                }
             }
         }
+    }
+}
 
-        new_image
+/// Whether a keyed or partly keyed pixel lies within `SPILL_RADIUS`
+fn near_key(mattes: &[f32], (x, y): (u32, u32), (width, height): (u32, u32)) -> bool {
+    let rows = y.saturating_sub(SPILL_RADIUS)..=(y + SPILL_RADIUS).min(height - 1);
+
+    rows.into_iter().any(|row| {
+        let start = (row * width) as usize;
+
+        mattes[start + x.saturating_sub(SPILL_RADIUS) as usize
+            ..=start + (x + SPILL_RADIUS).min(width - 1) as usize]
+            .iter()
+            .any(|matte| *matte < 1.0)
+    })
+}
+
+/// Clears pixels below the alpha threshold in place
+pub fn filter_alpha<T: Depth>(image: &mut RgbaBuffer<T>, alpha_filter: u8) {
+    let alpha_filter = T::from_unit(alpha_filter as f32 / 255.0);
+
+    for pixel in image.chunks_exact_mut(4) {
+        if pixel[3] < alpha_filter {
+            pixel.fill(T::zero());
+        }
     }
 }
 
-pub fn filter_alpha(image: &RgbaImage, alpha_filter: u8) -> RgbaImage {
+/// Average colour of the outermost pixel ring
+pub fn estimate_background<T: Depth>(image: &RgbaBuffer<T>) -> Rgba<u8> {
     let (width, height) = image.dimensions();
+    let mut sum = [0f64; 4];
+    let mut count = 0f64;
 
-    let mut new_image = RgbaImage::new(width, height);
-
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = image.get_pixel(x, y);
-
-            new_image.put_pixel(
-                x,
-                y,
-                if pixel.0[3] < alpha_filter {
-                    Rgba([0, 0, 0, 0])
-                } else {
-                    *pixel
-                },
-            );
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            for (channel, value) in sum.iter_mut().zip(pixel.channels()) {
+                *channel += value.to_unit() as f64;
+            }
+            count += 1.0;
         }
     }
 
-    new_image
+    Rgba(sum.map(|channel| (channel / count.max(1.0) * 255.0).round() as u8))
 }
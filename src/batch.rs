@@ -0,0 +1,233 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::log::{self, Level};
+use crate::status;
+
+/// Width of the progress bar in characters
+const BAR_WIDTH: usize = 30;
+
+pub enum Status {
+    Processed,
+    /// Reason, e.g. the output is up to date or the object touches the border
+    Skipped(String),
+    Failed(String),
+}
+
+/// What happened to one input and how long it took
+pub struct ImageResult {
+    pub input: String,
+    pub status: Status,
+    pub source_size: Option<(u32, u32)>,
+    pub output_size: Option<(u32, u32)>,
+    pub output: Option<String>,
+    pub time: Duration,
+}
+
+impl ImageResult {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.to_string(),
+            status: Status::Processed,
+            source_size: None,
+            output_size: None,
+            output: None,
+            time: Duration::ZERO,
+        }
+    }
+
+    pub fn failed(input: &str, message: String) -> Self {
+        Self {
+            status: Status::Failed(message),
+            ..Self::new(input)
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let size = |size: Option<(u32, u32)>| {
+            size.map(|(width, height)| json!({ "width": width, "height": height }))
+        };
+
+        let (status, reason, error) = match &self.status {
+            Status::Processed => ("processed", None, None),
+            Status::Skipped(reason) => ("skipped", Some(reason), None),
+            Status::Failed(error) => ("failed", None, Some(error)),
+        };
+
+        json!({
+            "input": self.input,
+            "status": status,
+            "seconds": self.time.as_secs_f64(),
+            "source_size": size(self.source_size),
+            "output_size": size(self.output_size),
+            "output": self.output,
+            "reason": reason,
+            "error": error,
+        })
+    }
+}
+
+/// Collects the results of a batch run and draws its progress
+pub struct Batch {
+    results: Vec<ImageResult>,
+    /// Inputs expected in total, including those that failed before processing
+    total: usize,
+    start: Instant,
+    progress: bool,
+}
+
+impl Batch {
+    /// `progress` draws a progress bar, only useful when stderr is a terminal
+    pub fn new(progress: bool) -> Self {
+        Self {
+            results: Vec::new(),
+            total: 0,
+            start: Instant::now(),
+            progress,
+        }
+    }
+
+    /// Adds inputs still to be processed to the total
+    pub fn expect(&mut self, inputs: usize) {
+        self.total = self.results.len() + inputs;
+        self.draw();
+    }
+
+    pub fn record(&mut self, result: ImageResult) {
+        let level = match result.status {
+            Status::Processed | Status::Skipped(_) => Level::Info,
+            Status::Failed(_) => Level::Error,
+        };
+        log::record(
+            level,
+            json!({ "event": "image", "image": result.to_json() }),
+        );
+
+        self.results.push(result);
+        self.total = self.total.max(self.results.len());
+        self.draw();
+    }
+
+    /// Inputs recorded so far
+    pub fn count(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Any input failed, the run exits with an error code
+    pub fn any_failed(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| matches!(result.status, Status::Failed(_)))
+    }
+
+    /// Removes the progress bar
+    pub fn finish(&self) {
+        if self.progress {
+            log::progress(String::new());
+        }
+    }
+
+    /// Processed, skipped and failed counts
+    fn counts(&self) -> (usize, usize, usize) {
+        self.results.iter().fold(
+            (0, 0, 0),
+            |(processed, skipped, failed), result| match result.status {
+                Status::Processed => (processed + 1, skipped, failed),
+                Status::Skipped(_) => (processed, skipped + 1, failed),
+                Status::Failed(_) => (processed, skipped, failed + 1),
+            },
+        )
+    }
+
+    fn average(&self) -> Duration {
+        if self.results.is_empty() {
+            Duration::ZERO
+        } else {
+            self.results
+                .iter()
+                .map(|result| result.time)
+                .sum::<Duration>()
+                / self.results.len() as u32
+        }
+    }
+
+    fn draw(&self) {
+        if !self.progress || self.total == 0 {
+            return;
+        }
+
+        let done = self.results.len();
+        let filled = BAR_WIDTH * done / self.total;
+        let elapsed = self.start.elapsed();
+
+        let eta = if done == 0 {
+            String::from("--")
+        } else {
+            duration(elapsed.mul_f64((self.total - done) as f64 / done as f64))
+        };
+
+        log::progress(format!(
+            "[{}{}] {done}/{} {}% ETA {eta}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            self.total,
+            100 * done / self.total,
+        ));
+    }
+
+    pub fn print_summary(&self) {
+        let (processed, skipped, failed) = self.counts();
+
+        if log::is_json() {
+            return log::record(
+                Level::Info,
+                json!({
+                    "event": "summary",
+                    "processed": processed,
+                    "skipped": skipped,
+                    "failed": failed,
+                    "seconds": self.start.elapsed().as_secs_f64(),
+                    "average_seconds": self.average().as_secs_f64(),
+                }),
+            );
+        }
+
+        status!("Processed  {processed}");
+        status!("Skipped    {skipped}");
+        status!("Failed     {failed}");
+        status!("Total time {}", duration(self.start.elapsed()));
+        status!("Average    {:.2}s per image", self.average().as_secs_f64());
+    }
+
+    /// Writes per image timings, sizes and errors as JSON
+    pub fn write_report(&self, path: &str) -> Result<(), String> {
+        let (processed, skipped, failed) = self.counts();
+
+        let report = json!({
+            "processed": processed,
+            "skipped": skipped,
+            "failed": failed,
+            "seconds": self.start.elapsed().as_secs_f64(),
+            "average_seconds": self.average().as_secs_f64(),
+            "images": self.results.iter().map(ImageResult::to_json).collect::<Vec<Value>>(),
+        });
+
+        let content = serde_json::to_string_pretty(&report)
+            .map_err(|err| format!("Failed to serialize report: {err}"))?;
+
+        fs::write(path, content + "\n").map_err(|err| format!("Failed to write {path}: {err}"))
+    }
+}
+
+/// `1m 05s` or `4.2s`
+fn duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
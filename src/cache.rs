@@ -0,0 +1,158 @@
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+
+use crate::warning;
+
+/// Manifest of processed images, kept next to the outputs
+pub const MANIFEST: &str = ".imoyo-cache";
+
+/// Hashes of the input and settings an output was made with, and of the output itself
+struct Entry {
+    image_name: String,
+    input_hash: String,
+    settings_hash: String,
+    output_hash: String,
+}
+
+/// Remembers which outputs are up to date, so repeated batch runs skip them.
+///
+/// One tab separated line per output file, a later line replaces an earlier one. Different
+/// settings can write the same output file, so only the last line for it is live.
+pub struct Cache {
+    entries: HashMap<String, Entry>,
+    /// Output file of each image name and settings hash
+    outputs: HashMap<(String, String), String>,
+    /// Opened for appending on the first saved output, so runs that save nothing leave no manifest
+    manifest: OnceCell<Option<File>>,
+    settings_hash: String,
+}
+
+impl Cache {
+    /// `settings` is every setting that changes the output
+    pub fn open(settings: &str) -> Self {
+        let mut cache = Self {
+            entries: HashMap::new(),
+            outputs: HashMap::new(),
+            manifest: OnceCell::new(),
+            settings_hash: blake3::hash(settings.as_bytes()).to_hex().to_string(),
+        };
+        let mut lines = 0;
+
+        if let Ok(content) = fs::read_to_string(MANIFEST) {
+            for line in content.lines() {
+                let fields = line.split('\t').collect::<Vec<&str>>();
+                lines += 1;
+
+                if let [output, image_name, input_hash, settings_hash, output_hash] = fields[..] {
+                    cache.add(
+                        output,
+                        Entry {
+                            image_name: image_name.to_string(),
+                            input_hash: input_hash.to_string(),
+                            settings_hash: settings_hash.to_string(),
+                            output_hash: output_hash.to_string(),
+                        },
+                    );
+                }
+            }
+        }
+
+        // Replaced and unreadable lines are dropped once they make up half of the manifest
+        if lines > 2 * cache.entries.len() {
+            if let Err(err) = compact(&cache.entries) {
+                warning!("Failed to compact {MANIFEST}: {err}");
+            }
+        }
+
+        cache
+    }
+
+    /// Existing output for an unchanged image processed with unchanged settings, as long as
+    /// the output file wasn't replaced since
+    pub fn output(&self, image_name: &str, input_hash: &str) -> Option<&str> {
+        let output = self
+            .outputs
+            .get(&(image_name.to_string(), self.settings_hash.clone()))?;
+        let entry = self.entries.get(output)?;
+
+        (entry.input_hash == input_hash
+            && hash_file(output).is_ok_and(|hash| hash == entry.output_hash))
+        .then_some(output.as_str())
+    }
+
+    /// Records a saved output, replacing the entry of whatever was written there before
+    pub fn insert(&mut self, image_name: &str, input_hash: &str, output: &str) {
+        let output_hash = match hash_file(output) {
+            Ok(hash) => hash,
+            Err(message) => {
+                warning!("{message}");
+                return;
+            }
+        };
+
+        let entry = Entry {
+            image_name: image_name.to_string(),
+            input_hash: input_hash.to_string(),
+            settings_hash: self.settings_hash.clone(),
+            output_hash,
+        };
+
+        let manifest = self.manifest.get_or_init(|| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(MANIFEST)
+                .map_err(|err| warning!("Failed to open {MANIFEST}, caching is off: {err}"))
+                .ok()
+        });
+
+        if let Some(mut manifest) = manifest.as_ref() {
+            if let Err(err) = writeln!(manifest, "{}", line(output, &entry)) {
+                warning!("Failed to write {MANIFEST}: {err}");
+            }
+        }
+
+        self.add(output, entry);
+    }
+
+    fn add(&mut self, output: &str, entry: Entry) {
+        if let Some(replaced) = self.entries.get(output) {
+            self.outputs
+                .remove(&(replaced.image_name.clone(), replaced.settings_hash.clone()));
+        }
+
+        self.outputs.insert(
+            (entry.image_name.clone(), entry.settings_hash.clone()),
+            output.to_string(),
+        );
+        self.entries.insert(output.to_string(), entry);
+    }
+}
+
+/// Content hash of a file
+pub fn hash_file(path: &str) -> Result<String, String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut file = File::open(path).map_err(|err| format!("Failed to open {path}: {err}"))?;
+
+    io::copy(&mut file, &mut hasher).map_err(|err| format!("Failed to read {path}: {err}"))?;
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn line(output: &str, entry: &Entry) -> String {
+    format!(
+        "{output}\t{}\t{}\t{}\t{}",
+        entry.image_name, entry.input_hash, entry.settings_hash, entry.output_hash
+    )
+}
+
+fn compact(entries: &HashMap<String, Entry>) -> io::Result<()> {
+    let content = entries
+        .iter()
+        .map(|(output, entry)| line(output, entry) + "\n")
+        .collect::<String>();
+
+    fs::write(MANIFEST, content)
+}
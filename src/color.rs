@@ -0,0 +1,134 @@
+use image::{DynamicImage, ImageBuffer};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+/// How an embedded ICC profile is handled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IccMode {
+    /// Convert pixels to sRGB and drop the profile
+    Convert,
+    /// Keep pixels as they are and embed the profile into the output
+    Preserve,
+}
+
+/// Converts RGB and grey images to sRGB, other profile colour spaces such as CMYK or Lab are an error
+pub fn convert_to_srgb(image: &DynamicImage, icc_profile: &[u8]) -> Result<DynamicImage, String> {
+    let source = ColorProfile::new_from_slice(icc_profile)
+        .map_err(|err| format!("Invalid ICC profile: {err}"))?;
+    let srgb = ColorProfile::new_srgb();
+
+    let (width, height) = (image.width(), image.height());
+    let source_layout = match source.color_space {
+        DataColorSpace::Rgb => Layout::Rgba,
+        DataColorSpace::Gray => Layout::GrayAlpha,
+        color_space => {
+            return Err(format!(
+                "Unsupported ICC profile colour space {color_space:?}"
+            ))
+        }
+    };
+    let options = TransformOptions::default();
+    let error = |err| format!("Failed to convert ICC profile to sRGB: {err}");
+
+    match image {
+        DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgb16(_)
+        | DynamicImage::ImageRgba16(_) => {
+            let pixels = source_pixels(
+                image,
+                source_layout,
+                |i| i.to_luma_alpha16().into_raw(),
+                |i| i.to_rgba16().into_raw(),
+            );
+            let mut converted = vec![0u16; (width * height * 4) as usize];
+
+            source
+                .create_transform_16bit(source_layout, &srgb, Layout::Rgba, options)
+                .and_then(|transform| transform.transform(&pixels, &mut converted))
+                .map_err(error)?;
+
+            ImageBuffer::from_raw(width, height, converted).map(DynamicImage::ImageRgba16)
+        }
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            let pixels = source_pixels(
+                image,
+                source_layout,
+                |i| i.to_luma_alpha32f().into_raw(),
+                |i| i.to_rgba32f().into_raw(),
+            );
+            let mut converted = vec![0f32; (width * height * 4) as usize];
+
+            source
+                .create_transform_f32(source_layout, &srgb, Layout::Rgba, options)
+                .and_then(|transform| transform.transform(&pixels, &mut converted))
+                .map_err(error)?;
+
+            ImageBuffer::from_raw(width, height, converted).map(DynamicImage::ImageRgba32F)
+        }
+        _ => {
+            let pixels = source_pixels(
+                image,
+                source_layout,
+                |i| i.to_luma_alpha8().into_raw(),
+                |i| i.to_rgba8().into_raw(),
+            );
+            let mut converted = vec![0u8; (width * height * 4) as usize];
+
+            source
+                .create_transform_8bit(source_layout, &srgb, Layout::Rgba, options)
+                .and_then(|transform| transform.transform(&pixels, &mut converted))
+                .map_err(error)?;
+
+            ImageBuffer::from_raw(width, height, converted).map(DynamicImage::ImageRgba8)
+        }
+    }
+    .ok_or_else(|| String::from("Converted image has invalid dimensions"))
+}
+
+fn source_pixels<T>(
+    image: &DynamicImage,
+    layout: Layout,
+    gray: impl Fn(&DynamicImage) -> Vec<T>,
+    rgb: impl Fn(&DynamicImage) -> Vec<T>,
+) -> Vec<T> {
+    if layout == Layout::GrayAlpha {
+        gray(image)
+    } else {
+        rgb(image)
+    }
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// BT.601 luma with blue and red difference chroma of unit RGB
+pub fn to_ycbcr([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.168736 * r - 0.331264 * g + 0.5 * b,
+        0.5 * r - 0.418688 * g - 0.081312 * b,
+    ]
+}
+
+pub fn from_ycbcr([y, cb, cr]: [f32; 3]) -> [f32; 3] {
+    [
+        y + 1.402 * cr,
+        y - 0.344136 * cb - 0.714136 * cr,
+        y + 1.772 * cb,
+    ]
+}
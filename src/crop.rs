@@ -1,154 +1,802 @@
-use image::{DynamicImage, GrayImage, RgbaImage};
-use imageproc::edges;
+use std::borrow::Cow;
+use std::fmt;
 
+use image::{GrayImage, Pixel};
+
+use crate::args::EdgeDetectionSettings;
 use crate::background::Background;
+use crate::debug::DebugOutput;
+use crate::depth::{Depth, RgbaBuffer};
+use crate::edges;
+use crate::shadow::{self, ShadowMode};
+use crate::verbose;
 
 pub struct Crop {
-    padding: u32,
+    padding: Padding,
     bg: Background,
+    border_touch: Option<BorderTouch>,
+    shadow: Option<ShadowMode>,
+    /// Space below the object on the square canvas, percentages are of the canvas side
+    baseline: Option<Length>,
+}
+
+/// What to do when the object reaches the image border
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderTouch {
+    Warn,
+    Fail,
+    NoPad,
+}
+
+/// Padding of one side, percentages are of the object width or height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl Length {
+    fn resolve(&self, size: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => *pixels,
+            Length::Percent(percent) => (size as f32 * percent / 100.0).round() as u32,
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{pixels}"),
+            Length::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+/// Space added around the object on each side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Padding {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl Padding {
+    pub fn uniform(length: Length) -> Self {
+        Self {
+            top: length,
+            right: length,
+            bottom: length,
+            left: length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sides()
+            .iter()
+            .all(|side| matches!(side, Length::Pixels(0)) || side == &Length::Percent(0.0))
+    }
+
+    /// Pixels of a padding that is the same number of pixels on all sides
+    pub fn uniform_pixels(&self) -> Option<u32> {
+        match self.top {
+            Length::Pixels(pixels) if self.sides().iter().all(|side| side == &self.top) => {
+                Some(pixels)
+            }
+            _ => None,
+        }
+    }
+
+    fn sides(&self) -> [Length; 4] {
+        [self.top, self.right, self.bottom, self.left]
+    }
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Self::uniform(Length::Pixels(0))
+    }
+}
+
+/// `10` when all sides are equal, `top,right,bottom,left` otherwise
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.sides().iter().all(|side| side == &self.top) {
+            write!(f, "{}", self.top)
+        } else {
+            write!(
+                f,
+                "{},{},{},{}",
+                self.top, self.right, self.bottom, self.left
+            )
+        }
+    }
 }
 
-#[derive(Debug)]
-struct ObjectInfo {
-    min_x: i32,
-    min_y: i32,
-    max_x: i32,
-    max_y: i32,
+/// What to do when `--crop-to` can't keep the whole object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutPolicy {
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sides {
+    pub top: bool,
+    pub right: bool,
+    pub bottom: bool,
+    pub left: bool,
+}
+
+impl Sides {
+    pub fn any(&self) -> bool {
+        self.top || self.right || self.bottom || self.left
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.top, "top"),
+            (self.right, "right"),
+            (self.bottom, "bottom"),
+            (self.left, "left"),
+        ]
+        .into_iter()
+        .filter_map(|(touches, name)| touches.then_some(name))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectInfo {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+/// Cell size of the detection proxy in pixels
+const PROXY_STEP: usize = 8;
+
+/// Inclusive range of object pixel coordinates along one axis
+type Range = (u32, u32);
+
+impl ObjectInfo {
+    fn from_ranges(ranges: (Option<Range>, Option<Range>)) -> Option<Self> {
+        match ranges {
+            (Some((min_x, max_x)), Some((min_y, max_y))) => Some(Self {
+                min_x: min_x as i32,
+                min_y: min_y as i32,
+                max_x: max_x as i32,
+                max_y: max_y as i32,
+            }),
+            _ => None,
+        }
+    }
+
+    fn ranges(bounds: Option<&Self>) -> (Option<Range>, Option<Range>) {
+        bounds.map_or((None, None), |b| {
+            (
+                Some((b.min_x as u32, b.max_x as u32)),
+                Some((b.min_y as u32, b.max_y as u32)),
+            )
+        })
+    }
+
+    /// Bounds are always stretched to include the image centre
+    fn around_centre(width: u32, height: u32, ranges: (Option<Range>, Option<Range>)) -> Self {
+        let (x_range, y_range) = ranges;
+        let (centre_x, centre_y) = (width / 2, height / 2);
+
+        let (min_x, max_x) = x_range.map_or((centre_x, centre_x), |(min, max)| {
+            (min.min(centre_x), max.max(centre_x))
+        });
+        let (min_y, max_y) = y_range.map_or((centre_y, centre_y), |(min, max)| {
+            (min.min(centre_y), max.max(centre_y))
+        });
+
+        Self {
+            min_x: min_x as i32,
+            min_y: min_y as i32,
+            max_x: max_x as i32,
+            max_y: max_y as i32,
+        }
+    }
+
+    /// Sides listed in `skip` are left unpadded
+    fn with_padding(self, padding: &Padding, skip: Sides) -> Self {
+        let (width, height) = (self.width(), self.height());
+        let side = |length: Length, size: u32, skipped: bool| {
+            if skipped {
+                0
+            } else {
+                length.resolve(size) as i32
+            }
+        };
+
+        Self {
+            min_x: self.min_x - side(padding.left, width, skip.left),
+            min_y: self.min_y - side(padding.top, height, skip.top),
+            max_x: self.max_x + side(padding.right, width, skip.right),
+            max_y: self.max_y + side(padding.bottom, height, skip.bottom),
+        }
+    }
+
+    pub fn touching_sides(&self, width: u32, height: u32) -> Sides {
+        Sides {
+            top: self.min_y <= 0,
+            right: self.max_x >= width as i32 - 1,
+            bottom: self.max_y >= height as i32 - 1,
+            left: self.min_x <= 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        (self.max_x - self.min_x + 1) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.max_y - self.min_y + 1) as u32
+    }
 }
 
 impl Crop {
-    pub fn new(padding: u32, bg: Background) -> Self {
-        Self { padding, bg }
+    pub fn new(padding: Padding, bg: Background, border_touch: Option<BorderTouch>) -> Self {
+        Self {
+            padding,
+            bg,
+            border_touch,
+            shadow: None,
+            baseline: None,
+        }
+    }
+
+    pub fn with_baseline(self, baseline: Option<Length>) -> Self {
+        Self { baseline, ..self }
+    }
+
+    pub fn with_shadow(self, shadow: Option<ShadowMode>) -> Self {
+        Self { shadow, ..self }
+    }
+
+    /// Makes cast shadows next to the object transparent
+    pub fn remove_shadow<T: Depth>(&self, image: &mut RgbaBuffer<T>) {
+        let shadow = shadow::detect(image, &self.bg);
+        shadow::clear(image, &shadow);
+    }
+
+    /// Image the bounds are detected on, without shadows when they are excluded
+    fn detection_image<'a, T: Depth>(&self, image: &'a RgbaBuffer<T>) -> Cow<'a, RgbaBuffer<T>> {
+        if self.shadow == Some(ShadowMode::Exclude) {
+            let mut image = image.clone();
+            self.remove_shadow(&mut image);
+            Cow::Owned(image)
+        } else {
+            Cow::Borrowed(image)
+        }
+    }
+
+    /// Returns the cropped image and the image sides the object touches
+    pub fn crop_to_object<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        debug: Option<&DebugOutput>,
+    ) -> Result<(RgbaBuffer<T>, Sides), String> {
+        let (object, touching) = self.crop_region(
+            image.dimensions(),
+            self.object_ranges(&self.detection_image(image)),
+        )?;
+
+        if let Some(debug) = debug {
+            debug.save_mask(image, &self.bg);
+            debug.save_object("crop", image, &object);
+        }
+
+        Ok((self.get_object(image, object), touching))
+    }
+
+    /// Same result as `crop_to_object` for an image that is only converted `strip_rows` rows at
+    /// a time, `rows(y, count)` returns full width rows from `y` in the working format.
+    ///
+    /// `check` gets the size of the crop region before it is allocated.
+    pub fn crop_in_strips<T: Depth>(
+        &self,
+        (width, height): (u32, u32),
+        strip_rows: u32,
+        rows: impl Fn(u32, u32) -> RgbaBuffer<T>,
+        check: impl Fn(u32, u32) -> Result<(), String>,
+    ) -> Result<(RgbaBuffer<T>, Sides), String> {
+        let mut x_range: Option<Range> = None;
+        let mut y_range: Option<Range> = None;
+
+        for start in (0..height).step_by(strip_rows as usize) {
+            let end = (start + strip_rows).min(height);
+
+            // One more row on each side, so the neighbour checks see what a full scan sees
+            let first = start.saturating_sub(1);
+            let strip = rows(first, (end + 1).min(height) - first);
+            let (strip_x, strip_y) = self.object_ranges_in(&strip, start - first..end - first);
+
+            if let Some((min, max)) = strip_x {
+                extend(&mut x_range, min);
+                extend(&mut x_range, max);
+            }
+            if let Some((min, max)) = strip_y {
+                extend(&mut y_range, first + min);
+                extend(&mut y_range, first + max);
+            }
+        }
+
+        let (object, touching) = self.crop_region((width, height), (x_range, y_range))?;
+        check(object.width(), object.height())?;
+
+        let mut object_image =
+            RgbaBuffer::<T>::from_pixel(object.width(), object.height(), self.bg.pixel::<T>());
+
+        // Part of the padded object that lies inside the image, the rest stays background
+        let (min_x, min_y) = (object.min_x.max(0) as u32, object.min_y.max(0) as u32);
+        let (max_x, max_y) = (
+            object.max_x.min(width as i32 - 1) as u32,
+            object.max_y.min(height as i32 - 1) as u32,
+        );
+
+        for start in (min_y..=max_y).step_by(strip_rows as usize) {
+            let count = strip_rows.min(max_y + 1 - start);
+
+            copy_rows(
+                &rows(start, count),
+                (min_x, 0),
+                &mut object_image,
+                (
+                    (min_x as i32 - object.min_x) as u32,
+                    (start as i32 - object.min_y) as u32,
+                ),
+                (max_x - min_x + 1, count),
+            );
+        }
+
+        Ok((object_image, touching))
+    }
+
+    /// Padded crop bounds of the object in an image of the given size and the sides it touches
+    fn crop_region(
+        &self,
+        size: (u32, u32),
+        ranges: (Option<Range>, Option<Range>),
+    ) -> Result<(ObjectInfo, Sides), String> {
+        let (region, touching) = self.padded_region(size, ranges);
+
+        if touching.any() && self.border_touch == Some(BorderTouch::Fail) {
+            return Err(format!(
+                "Object touches image border: {}",
+                touching.names().join(", ")
+            ));
+        }
+
+        Ok((region, touching))
     }
 
-    pub fn crop_to_object(&self, image: &RgbaImage) -> RgbaImage {
-        let object = self.object_info(image);
+    fn padded_region(
+        &self,
+        (width, height): (u32, u32),
+        ranges: (Option<Range>, Option<Range>),
+    ) -> (ObjectInfo, Sides) {
+        let object = ObjectInfo::around_centre(width, height, ranges);
+        let touching = object.touching_sides(width, height);
+
+        let skip = if self.border_touch == Some(BorderTouch::NoPad) {
+            touching
+        } else {
+            Sides::default()
+        };
+
+        (object.with_padding(&self.padding, skip), touching)
+    }
+
+    /// Region `crop_to_object` cuts for `bounds` found by `detect`, padded and stretched to
+    /// the image centre, it can reach past the image border
+    pub fn object_region(&self, size: (u32, u32), bounds: Option<&ObjectInfo>) -> ObjectInfo {
+        self.padded_region(size, ObjectInfo::ranges(bounds)).0
+    }
+
+    /// Region `crop_to_edges_canny` cuts for `bounds` found by `detect_edges`
+    pub fn edge_region(&self, size: (u32, u32), bounds: Option<&ObjectInfo>) -> ObjectInfo {
+        self.edge_box(size, ObjectInfo::ranges(bounds))
+    }
+
+    fn edge_box(
+        &self,
+        (width, height): (u32, u32),
+        ranges: (Option<Range>, Option<Range>),
+    ) -> ObjectInfo {
+        ObjectInfo::around_centre(width, height, ranges)
+            .with_padding(&self.padding, Sides::default())
+    }
+
+    /// Tight bounds of the object, `None` if no object pixels were found
+    pub fn detect<T: Depth>(&self, image: &RgbaBuffer<T>) -> Option<ObjectInfo> {
+        ObjectInfo::from_ranges(self.object_ranges(&self.detection_image(image)))
+    }
+
+    /// Same bounds as `detect` from a scan of every pixel, kept as the reference for benches
+    pub fn detect_full_scan<T: Depth>(&self, image: &RgbaBuffer<T>) -> Option<ObjectInfo> {
+        ObjectInfo::from_ranges(self.object_ranges_full(image))
+    }
+
+    /// Finds the cells of a 1/`PROXY_STEP` scale proxy that hold any non background pixel, then
+    /// refines each edge at full resolution from the border of those cells inwards.
+    ///
+    /// The proxy never misses a pixel, so no object lies outside its cells and the result equals
+    /// a full scan. On a clean background each edge is found within the first band of
+    /// `PROXY_STEP` pixels, specks next to the object only make the band wider. The object
+    /// interior is skipped once a cell is known to be occupied.
+    fn object_ranges<T: Depth>(&self, image: &RgbaBuffer<T>) -> (Option<Range>, Option<Range>) {
+        self.object_ranges_in(image, 0..image.height())
+    }
+
+    /// `object_ranges` of the given rows, the rows around them are only read by neighbour checks
+    fn object_ranges_in<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        rows: std::ops::Range<u32>,
+    ) -> (Option<Range>, Option<Range>) {
+        let width = image.width();
+        let step = PROXY_STEP as u32;
+        let columns = width.div_ceil(step) as usize;
+
+        let mut occupied = vec![false; columns * rows.len().div_ceil(PROXY_STEP)];
+
+        for (y, row) in image
+            .chunks_exact((width as usize * 4).max(1))
+            .skip(rows.start as usize)
+            .take(rows.len())
+            .enumerate()
+        {
+            let start = y / PROXY_STEP * columns;
+            let cells = &mut occupied[start..start + columns];
+
+            for (cell, pixels) in cells.iter_mut().zip(row.chunks(PROXY_STEP * 4)) {
+                *cell = *cell
+                    || pixels
+                        .chunks_exact(4)
+                        .any(|pixel| !self.bg.is_background::<T>(*T::Rgba::from_slice(pixel)));
+            }
+        }
+
+        let mut cell_x: Option<Range> = None;
+        let mut cell_y: Option<Range> = None;
 
-        self.get_object(image, object)
+        for (i, _) in occupied
+            .iter()
+            .enumerate()
+            .filter(|(_, occupied)| **occupied)
+        {
+            extend(&mut cell_x, (i % columns) as u32);
+            extend(&mut cell_y, (i / columns) as u32);
+        }
+
+        // Only background pixels, so there is no object either
+        let (Some((cell_min_x, cell_max_x)), Some((cell_min_y, cell_max_y))) = (cell_x, cell_y)
+        else {
+            return (None, None);
+        };
+
+        let xs = cell_min_x * step..((cell_max_x + 1) * step).min(width);
+        let ys =
+            rows.start + cell_min_y * step..(rows.start + (cell_max_y + 1) * step).min(rows.end);
+
+        let has_object_row = |y: u32| xs.clone().any(|x| self.is_object_row(image, x, y));
+        let has_object_column = |x: u32| ys.clone().any(|y| self.is_object_column(image, x, y));
+
+        let y_range = ys
+            .clone()
+            .find(|&y| has_object_row(y))
+            .zip(ys.clone().rev().find(|&y| has_object_row(y)));
+        let x_range = xs
+            .clone()
+            .find(|&x| has_object_column(x))
+            .zip(xs.clone().rev().find(|&x| has_object_column(x)));
+
+        (x_range, y_range)
     }
 
-    fn object_info(&self, image: &RgbaImage) -> ObjectInfo {
+    fn object_ranges_full<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+    ) -> (Option<Range>, Option<Range>) {
         let (width, height) = image.dimensions();
 
-        let mut min_x = width / 2;
-        let mut min_y = height / 2;
-        let mut max_x = width / 2;
-        let mut max_y = height / 2;
+        let mut x_range: Option<Range> = None;
+        let mut y_range: Option<Range> = None;
 
         for y in 0..height {
             for x in 0..width {
-                if !self.bg.is_background(*image.get_pixel(x, y)) {
-                    if x < min_x && !self.bg.is_column_neighbours_white(image, x, y) {
-                        min_x = x;
-                    }
-                    if y < min_y && !self.bg.is_row_neighbours_white(image, x, y) {
-                        min_y = y;
-                    }
-                    if x > max_x && !self.bg.is_column_neighbours_white(image, x, y) {
-                        max_x = x;
-                    }
-                    if y > max_y && !self.bg.is_row_neighbours_white(image, x, y) {
-                        max_y = y;
-                    }
+                if self.is_object_column(image, x, y) {
+                    extend(&mut x_range, x);
+                }
+                if self.is_object_row(image, x, y) {
+                    extend(&mut y_range, y);
                 }
             }
         }
 
-        let max_x: i32 = max_x as i32 + self.padding as i32;
-        let min_x: i32 = min_x as i32 - self.padding as i32;
-        let max_y: i32 = max_y as i32 + self.padding as i32;
-        let min_y: i32 = min_y as i32 - self.padding as i32;
+        (x_range, y_range)
+    }
+
+    /// Object pixel that counts for the horizontal bounds
+    fn is_object_column<T: Depth>(&self, image: &RgbaBuffer<T>, x: u32, y: u32) -> bool {
+        !self.bg.is_background::<T>(*image.get_pixel(x, y))
+            && !self.bg.is_column_neighbours_white(image, x, y)
+    }
+
+    /// Object pixel that counts for the vertical bounds
+    fn is_object_row<T: Depth>(&self, image: &RgbaBuffer<T>, x: u32, y: u32) -> bool {
+        !self.bg.is_background::<T>(*image.get_pixel(x, y))
+            && !self.bg.is_row_neighbours_white(image, x, y)
+    }
 
-        ObjectInfo {
-            min_x,
-            min_y,
-            max_x,
-            max_y,
+    /// Adds the padding around the whole image, for when it isn't cropped
+    pub fn pad<T: Depth>(&self, image: RgbaBuffer<T>) -> RgbaBuffer<T> {
+        let object = ObjectInfo {
+            min_x: 0,
+            min_y: 0,
+            max_x: image.width() as i32 - 1,
+            max_y: image.height() as i32 - 1,
         }
+        .with_padding(&self.padding, Sides::default());
+
+        self.get_object(&image, object)
     }
 
-    fn get_object(&self, image: &RgbaImage, object: ObjectInfo) -> RgbaImage {
+    fn get_object<T: Depth>(&self, image: &RgbaBuffer<T>, object: ObjectInfo) -> RgbaBuffer<T> {
         let (width, height) = image.dimensions();
-        let (width_new, height_new) = (
-            (object.max_x - object.min_x + 1) as u32,
-            (object.max_y - object.min_y + 1) as u32,
+        let mut object_image =
+            RgbaBuffer::<T>::from_pixel(object.width(), object.height(), self.bg.pixel::<T>());
+
+        // Part of the padded object that lies inside the image, the rest stays background
+        let (min_x, min_y) = (object.min_x.max(0), object.min_y.max(0));
+        let (max_x, max_y) = (
+            object.max_x.min(width as i32 - 1),
+            object.max_y.min(height as i32 - 1),
         );
-        let mut object_image = RgbaImage::new(width_new, height_new);
-
-        for (x, y, pixel) in object_image.enumerate_pixels_mut() {
-            if x as i32 + object.min_x < 0
-                || x as i32 + object.min_x >= width as i32
-                || y as i32 + object.min_y < 0
-                || y as i32 + object.min_y >= height as i32
-            {
-                *pixel = self.bg.color;
-            } else {
-                *pixel = *image.get_pixel(
-                    (x as i32 + object.min_x) as u32,
-                    (y as i32 + object.min_y) as u32,
-                );
-            }
+
+        if min_x <= max_x && min_y <= max_y {
+            copy_rows(
+                image,
+                (min_x as u32, min_y as u32),
+                &mut object_image,
+                ((min_x - object.min_x) as u32, (min_y - object.min_y) as u32),
+                ((max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32),
+            );
         }
 
         object_image
     }
 
-    pub fn fill_to_square(&self, image: &RgbaImage) -> RgbaImage {
+    /// Crops to the aspect ratio `ratio_width:ratio_height` without adding any background.
+    ///
+    /// The window is the smallest one around the padded object, placed on the object's centre
+    /// of mass. If the object doesn't fit, the window keeps as many object pixels as possible
+    /// and the sides where the object is cut are returned.
+    pub fn crop_to_ratio<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        (ratio_width, ratio_height): (u32, u32),
+    ) -> (RgbaBuffer<T>, Sides) {
+        let (width, height) = image.dimensions();
+        let ratio = ratio_width as f32 / ratio_height as f32;
+
+        let detection_image = self.detection_image(image);
+        let mut columns = vec![0u32; width as usize];
+        let mut rows = vec![0u32; height as usize];
+
+        for (x, y, pixel) in detection_image.enumerate_pixels() {
+            if !self.bg.is_background::<T>(*pixel) {
+                columns[x as usize] += 1;
+                rows[y as usize] += 1;
+            }
+        }
+
+        // Without an object the window is the largest one of the ratio, centred, and cuts nothing
+        let found = ObjectInfo::from_ranges(self.object_ranges(&detection_image));
+        let object = found.unwrap_or(ObjectInfo {
+            min_x: 0,
+            min_y: 0,
+            max_x: width as i32 - 1,
+            max_y: height as i32 - 1,
+        });
+        let padded = object.with_padding(&self.padding, Sides::default());
+
+        // Largest window of the ratio that fits into the image
+        let (max_width, max_height) = if width as f32 / height as f32 > ratio {
+            (((height as f32 * ratio).round() as u32).max(1), height)
+        } else {
+            (width, ((width as f32 / ratio).round() as u32).max(1))
+        };
+
+        let window_width = padded
+            .width()
+            .max((padded.height() as f32 * ratio).ceil() as u32);
+        let (window_width, window_height) = if window_width > max_width
+            || (window_width as f32 / ratio).round() as u32 > max_height
+        {
+            (max_width, max_height)
+        } else {
+            (
+                window_width,
+                ((window_width as f32 / ratio).round() as u32).max(1),
+            )
+        };
+
+        let clamp =
+            |min: i32, max: i32, size: u32| (min.max(0) as u32, max.min(size as i32 - 1) as u32);
+        let start_x = place_window(
+            &columns,
+            clamp(padded.min_x, padded.max_x, width),
+            window_width,
+        );
+        let start_y = place_window(
+            &rows,
+            clamp(padded.min_y, padded.max_y, height),
+            window_height,
+        );
+
+        let window = ObjectInfo {
+            min_x: start_x as i32,
+            min_y: start_y as i32,
+            max_x: (start_x + window_width) as i32 - 1,
+            max_y: (start_y + window_height) as i32 - 1,
+        };
+        let cut = found.map_or(Sides::default(), |object| Sides {
+            top: window.min_y > object.min_y,
+            right: window.max_x < object.max_x,
+            bottom: window.max_y < object.max_y,
+            left: window.min_x > object.min_x,
+        });
+
+        (self.get_object(image, window), cut)
+    }
+
+    /// Centres the image on a square canvas, or anchors the object to the baseline if one is set
+    pub fn fill_to_square<T: Depth>(&self, image: RgbaBuffer<T>) -> RgbaBuffer<T> {
+        if let Some(margin) = self.baseline {
+            if let Some(object) = self.detect(&image) {
+                return self.anchor_to_baseline(&image, &object, margin);
+            }
+        }
+
         let (width, height) = image.dimensions();
 
         if width == height {
-            return image.clone();
+            return image;
         }
 
         let side = width.max(height);
-        let mut square_image = RgbaImage::new(side, side);
+        let mut square_image = RgbaBuffer::<T>::from_pixel(side, side, self.bg.pixel::<T>());
         let padding_y = ((side - height) as f32 / 2.0).ceil() as u32;
         let padding_x = ((side - width) as f32 / 2.0).ceil() as u32;
 
-        for (x, y, pixel) in square_image.enumerate_pixels_mut() {
-            if x < padding_x || x >= side - padding_x || y < padding_y || y >= side - padding_y {
-                *pixel = self.bg.color;
-            } else {
-                *pixel = *image.get_pixel(x - padding_x, y - padding_y);
+        // Padding is rounded up on both sides, so odd differences drop the last row or column
+        copy_rows(
+            &image,
+            (0, 0),
+            &mut square_image,
+            (padding_x, padding_y),
+            (side - 2 * padding_x, side - 2 * padding_y),
+        );
+
+        square_image
+    }
+
+    /// Square canvas with the object bottom `margin` above the lower edge and its centre of mass
+    /// in the middle, the canvas grows when the object wouldn't fit
+    fn anchor_to_baseline<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        object: &ObjectInfo,
+        margin: Length,
+    ) -> RgbaBuffer<T> {
+        let (width, height) = image.dimensions();
+        let centre_x = self.centre_of_mass_x(image, object);
+
+        let half = (centre_x - object.min_x as f32)
+            .max(object.max_x as f32 + 1.0 - centre_x)
+            .ceil() as u32;
+        let side = width.max(height).max(2 * half);
+        let side = match margin {
+            Length::Pixels(pixels) => side.max(object.height() + pixels),
+            Length::Percent(percent) => {
+                side.max((object.height() as f32 / (1.0 - percent / 100.0)).ceil() as u32)
             }
+        };
+
+        let min_x = centre_x.round() as i32 - (side / 2) as i32;
+        let max_y = object.max_y + margin.resolve(side) as i32;
+
+        self.get_object(
+            image,
+            ObjectInfo {
+                min_x,
+                min_y: max_y - side as i32 + 1,
+                max_x: min_x + side as i32 - 1,
+                max_y,
+            },
+        )
+    }
+
+    /// Horizontal centre of the object pixels weighted by opacity, the bounds centre if there are none
+    fn centre_of_mass_x<T: Depth>(&self, image: &RgbaBuffer<T>, object: &ObjectInfo) -> f32 {
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+
+        for y in object.min_y as u32..=object.max_y as u32 {
+            for x in object.min_x as u32..=object.max_x as u32 {
+                let pixel = *image.get_pixel(x, y);
+
+                if !self.bg.is_background::<T>(pixel) {
+                    let weight = pixel.channels()[3].to_unit();
+                    weighted += weight * (x as f32 + 0.5);
+                    total += weight;
+                }
+            }
+        }
+
+        if total > 0.0 {
+            weighted / total
+        } else {
+            (object.min_x + object.max_x + 1) as f32 / 2.0
         }
-        square_image.clone()
     }
 
-    pub fn crop_to_edges_canny(
+    pub fn crop_to_edges_canny<T: Depth>(
         &self,
-        image: &RgbaImage,
-        low_threshold: f32,
-        high_threshold: f32,
-        save_edges: bool,
-    ) -> RgbaImage {
-        let gray_image: GrayImage = DynamicImage::ImageRgba8(image.clone()).to_luma8();
-        let edges = edges::canny(&gray_image, low_threshold, high_threshold);
-        if save_edges {
-            edges.save("edges.jpg").unwrap();
+        image: &RgbaBuffer<T>,
+        settings: &EdgeDetectionSettings,
+        debug: Option<&DebugOutput>,
+    ) -> RgbaBuffer<T> {
+        let gray_image = T::to_luma8(image);
+        let (edges, low_threshold, high_threshold) = edges::canny(&gray_image, settings);
+        verbose!("Canny thresholds: {low_threshold}, {high_threshold}");
+
+        let object = self.object_info_gray(&edges);
+
+        if let Some(debug) = debug {
+            debug.save_edges(&edges);
+            debug.save_object("edges", image, &object);
         }
 
-        self.get_obj(image, &edges)
+        self.get_obj(image, &edges, object)
     }
 
-    fn get_obj(&self, image: &RgbaImage, edges: &GrayImage) -> RgbaImage {
-        let object = self.object_info_gray(edges);
-        let (width_new, height_new) = (
-            (object.max_x - object.min_x + 1) as u32,
-            (object.max_y - object.min_y + 1) as u32,
+    fn get_obj<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        edges: &GrayImage,
+        object: ObjectInfo,
+    ) -> RgbaBuffer<T> {
+        let (width, height) = image.dimensions();
+        let (width_new, height_new) = (object.width(), object.height());
+        let mut object_image =
+            RgbaBuffer::<T>::from_pixel(width_new, height_new, self.bg.pixel::<T>());
+
+        // Padding can reach past the image border, only the part inside the image is copied
+        let (min_x, min_y) = (object.min_x.max(0), object.min_y.max(0));
+        let (max_x, max_y) = (
+            object.max_x.min(width as i32 - 1),
+            object.max_y.min(height as i32 - 1),
         );
-        let mut object_image = RgbaImage::new(width_new, height_new);
+        let columns = (min_x..=max_x)
+            .map(|original_x| ((original_x - object.min_x) as u32, original_x as u32));
 
-        for y in 0..height_new {
+        for original_y in min_y..=max_y {
             let mut leftmost = false;
 
-            let original_y = (y as i32 + object.min_y) as u32;
+            let y = (original_y - object.min_y) as u32;
+            let original_y = original_y as u32;
 
             let mut rightmost = 0;
 
-            for x in 0..width_new {
-                let original_x = (x as i32 + object.min_x) as u32;
-
+            for (x, original_x) in columns.clone() {
                 if edges.get_pixel(original_x, original_y)[0] == u8::MAX
                     || self.are_neighbours_edges(edges, original_x, original_y)
                 {
@@ -156,17 +804,13 @@ impl Crop {
                 }
             }
 
-            for x in 0..width_new {
-                let original_x = (x as i32 + object.min_x) as u32;
-
+            for (x, original_x) in columns.clone() {
                 if !leftmost && edges.get_pixel(original_x, original_y)[0] == u8::MAX
                     || self.are_neighbours_edges(edges, original_x, original_y)
                 {
                     leftmost = true;
                     object_image.put_pixel(x, y, *image.get_pixel(original_x, original_y));
-                } else if !leftmost || x > rightmost {
-                    object_image.put_pixel(x, y, self.bg.color);
-                } else {
+                } else if leftmost && x <= rightmost {
                     object_image.put_pixel(x, y, *image.get_pixel(original_x, original_y));
                 }
             }
@@ -178,8 +822,8 @@ impl Crop {
     fn are_neighbours_edges(&self, image: &GrayImage, x: u32, y: u32) -> bool {
         let radius = 2;
 
-        for i in x.saturating_sub(radius)..=x.saturating_add(radius).min(image.width()) {
-            for j in y.saturating_sub(radius)..=y.saturating_add(radius).min(image.height()) {
+        for i in x.saturating_sub(radius)..=x.saturating_add(radius).min(image.width() - 1) {
+            for j in y.saturating_sub(radius)..=y.saturating_add(radius).min(image.height() - 1) {
                 if j == y {
                     break;
                 }
@@ -192,42 +836,106 @@ impl Crop {
         false
     }
 
+    /// Tight bounds of the detected edges, `None` if there are no edges
+    pub fn detect_edges<T: Depth>(
+        &self,
+        image: &RgbaBuffer<T>,
+        settings: &EdgeDetectionSettings,
+    ) -> Option<ObjectInfo> {
+        let gray_image = T::to_luma8(image);
+        let (edges, _, _) = edges::canny(&gray_image, settings);
+
+        ObjectInfo::from_ranges(Self::edge_ranges(&edges))
+    }
+
     fn object_info_gray(&self, image: &GrayImage) -> ObjectInfo {
+        self.edge_box(image.dimensions(), Self::edge_ranges(image))
+    }
+
+    fn edge_ranges(image: &GrayImage) -> (Option<Range>, Option<Range>) {
         let (width, height) = image.dimensions();
-        let mut min_x = width / 2;
-        let mut min_y = height / 2;
-        let mut max_x = width / 2;
-        let mut max_y = height / 2;
+
+        let mut x_range: Option<Range> = None;
+        let mut y_range: Option<Range> = None;
 
         for y in 0..height {
             for x in 0..width {
                 if image.get_pixel(x, y)[0] != 0 {
-                    if x < min_x {
-                        min_x = x;
-                    }
-                    if y < min_y {
-                        min_y = y;
-                    }
-                    if x > max_x {
-                        max_x = x;
-                    }
-                    if y > max_y {
-                        max_y = y;
-                    }
+                    extend(&mut x_range, x);
+                    extend(&mut y_range, y);
                 }
             }
         }
 
-        let max_x: i32 = max_x as i32 + self.padding as i32;
-        let min_x: i32 = min_x as i32 - self.padding as i32;
-        let max_y: i32 = max_y as i32 + self.padding as i32;
-        let min_y: i32 = min_y as i32 - self.padding as i32;
+        (x_range, y_range)
+    }
+}
+
+/// Copies a `width` x `height` block as whole row slices
+fn copy_rows<T: Depth>(
+    source: &RgbaBuffer<T>,
+    (source_x, source_y): (u32, u32),
+    target: &mut RgbaBuffer<T>,
+    (target_x, target_y): (u32, u32),
+    (width, height): (u32, u32),
+) {
+    let source_stride = source.width() as usize * 4;
+    let target_stride = target.width() as usize * 4;
+    let row = width as usize * 4;
+    let (source, target): (&[T], &mut [T]) = (source, target);
+
+    for y in 0..height as usize {
+        let from = (source_y as usize + y) * source_stride + source_x as usize * 4;
+        let to = (target_y as usize + y) * target_stride + target_x as usize * 4;
+
+        target[to..to + row].copy_from_slice(&source[from..from + row]);
+    }
+}
+
+/// Start of a window of `length` along an axis, `weights` count the object pixels per column or row.
+///
+/// A window longer than the object range is centred on the weights and keeps the range inside,
+/// a shorter one covers the most weight within the range.
+pub fn place_window(weights: &[u32], (min, max): (u32, u32), length: u32) -> u32 {
+    let size = weights.len() as u32;
+
+    if length > max - min {
+        let total = weights.iter().map(|weight| *weight as f64).sum::<f64>();
+        let centre = if total > 0.0 {
+            weights
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| (i as f64 + 0.5) * *weight as f64)
+                .sum::<f64>()
+                / total
+        } else {
+            (min + max + 1) as f64 / 2.0
+        };
+
+        let start = (centre - length as f64 / 2.0).round() as i64;
+        let start = start.clamp(max as i64 + 1 - length as i64, min as i64);
+
+        return start.clamp(0, (size - length) as i64) as u32;
+    }
 
-        ObjectInfo {
-            min_x,
-            min_y,
-            max_x,
-            max_y,
+    let mut covered = weights[min as usize..(min + length) as usize]
+        .iter()
+        .sum::<u32>();
+    let (mut best, mut best_start) = (covered, min);
+
+    for start in min + 1..=max + 1 - length {
+        covered = covered + weights[(start + length - 1) as usize] - weights[start as usize - 1];
+
+        if covered > best {
+            (best, best_start) = (covered, start);
         }
     }
+
+    best_start
+}
+
+fn extend(range: &mut Option<Range>, value: u32) {
+    *range = Some(range.map_or((value, value), |(min, max)| {
+        (min.min(value), max.max(value))
+    }));
 }
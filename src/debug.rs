@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{GrayImage, Luma, Rgba};
+use imageproc::{drawing, rect::Rect};
+use serde_json::json;
+
+use crate::background::Background;
+use crate::crop::ObjectInfo;
+use crate::depth::{Depth, RgbaBuffer};
+use crate::error;
+
+const OVERLAY_COLOR: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+/// Diagnostic files of one image, failures to write them are logged and don't stop processing
+pub struct DebugOutput {
+    dir: PathBuf,
+    name: String,
+}
+
+impl DebugOutput {
+    /// Files are named after the image and a hash of its `source`, so equal names from
+    /// different folders don't overwrite each other
+    pub fn new(dir: &Path, name: &str, source: &str) -> Option<Self> {
+        if let Err(err) = fs::create_dir_all(dir) {
+            error!("Failed to create debug dir {dir:?}: {err}");
+            return None;
+        }
+
+        let hash = blake3::hash(source.as_bytes()).to_hex();
+
+        Some(Self {
+            dir: dir.to_path_buf(),
+            name: format!("{name}-{}", &hash[..8]),
+        })
+    }
+
+    pub fn save_mask<T: Depth>(&self, image: &RgbaBuffer<T>, bg: &Background) {
+        let mask = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            if bg.is_background::<T>(*image.get_pixel(x, y)) {
+                Luma([0])
+            } else {
+                Luma([u8::MAX])
+            }
+        });
+
+        self.save_gray(&mask, "mask");
+    }
+
+    pub fn save_edges(&self, edges: &GrayImage) {
+        self.save_gray(edges, "edges");
+    }
+
+    pub fn save_object<T: Depth>(&self, stage: &str, image: &RgbaBuffer<T>, object: &ObjectInfo) {
+        let mut overlay = T::into_dynamic(image.clone()).to_rgba8();
+        drawing::draw_hollow_rect_mut(
+            &mut overlay,
+            Rect::at(object.min_x, object.min_y).of_size(object.width(), object.height()),
+            OVERLAY_COLOR,
+        );
+
+        let path = self.path(&format!("{stage}-overlay"), "png");
+        if let Err(err) = overlay.save(&path) {
+            error!("Failed to save {path:?}: {err}");
+        }
+
+        let json = json!({
+            "stage": stage,
+            "source_width": image.width(),
+            "source_height": image.height(),
+            "min_x": object.min_x,
+            "min_y": object.min_y,
+            "max_x": object.max_x,
+            "max_y": object.max_y,
+            "width": object.width(),
+            "height": object.height(),
+        });
+
+        let path = self.path(&format!("{stage}-object"), "json");
+        if let Err(err) = fs::write(&path, format!("{json}\n")) {
+            error!("Failed to save {path:?}: {err}");
+        }
+    }
+
+    fn save_gray(&self, image: &GrayImage, suffix: &str) {
+        let path = self.path(suffix, "png");
+        if let Err(err) = image.save(&path) {
+            error!("Failed to save {path:?}: {err}");
+        }
+    }
+
+    fn path(&self, suffix: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}-{suffix}.{extension}", self.name))
+    }
+}
@@ -0,0 +1,211 @@
+use image::{
+    imageops, DynamicImage, GrayImage, ImageBuffer, Pixel, Primitive, Rgb, RgbImage, Rgba,
+};
+
+pub type RgbaBuffer<T> = ImageBuffer<<T as Depth>::Rgba, Vec<T>>;
+pub type RgbBuffer<T> = ImageBuffer<<T as Depth>::Rgb, Vec<T>>;
+
+/// Channel type the pipeline works in, images are only quantised to 8 bit when encoded
+pub trait Depth: Primitive + 'static {
+    type Rgba: Pixel<Subpixel = Self> + 'static;
+    type Rgb: Pixel<Subpixel = Self> + 'static;
+
+    fn from_dynamic(image: DynamicImage) -> RgbaBuffer<Self>;
+
+    fn into_dynamic(image: RgbaBuffer<Self>) -> DynamicImage;
+
+    /// Channel value scaled to `0.0..=1.0`
+    fn to_unit(self) -> f32;
+
+    /// Channel value from `0.0..=1.0`, integer channels are rounded
+    fn from_unit(value: f32) -> Self;
+
+    /// Largest channel value as `f32`
+    fn max_f32() -> f32;
+
+    /// Native channel value, integer channels are rounded
+    fn from_f32(value: f32) -> Self;
+
+    fn as_f32(self) -> f32;
+
+    /// Reduces the image to 8 bit with Floyd–Steinberg dithering
+    fn to_rgb8(image: RgbBuffer<Self>) -> RgbImage {
+        dither(&image)
+    }
+
+    /// 8 bit luma for edge detection
+    fn to_luma8(image: &RgbaBuffer<Self>) -> GrayImage {
+        Self::into_dynamic(image.clone()).to_luma8()
+    }
+}
+
+impl Depth for u8 {
+    type Rgba = Rgba<u8>;
+    type Rgb = Rgb<u8>;
+
+    fn from_dynamic(image: DynamicImage) -> RgbaBuffer<Self> {
+        image.into_rgba8()
+    }
+
+    fn into_dynamic(image: RgbaBuffer<Self>) -> DynamicImage {
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn to_unit(self) -> f32 {
+        self as f32 / 255.0
+    }
+
+    fn from_unit(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+
+    fn max_f32() -> f32 {
+        255.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round() as u8
+    }
+
+    fn as_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn to_rgb8(image: RgbBuffer<Self>) -> RgbImage {
+        image
+    }
+
+    fn to_luma8(image: &RgbaBuffer<Self>) -> GrayImage {
+        imageops::grayscale(image)
+    }
+}
+
+impl Depth for u16 {
+    type Rgba = Rgba<u16>;
+    type Rgb = Rgb<u16>;
+
+    fn from_dynamic(image: DynamicImage) -> RgbaBuffer<Self> {
+        image.into_rgba16()
+    }
+
+    fn into_dynamic(image: RgbaBuffer<Self>) -> DynamicImage {
+        DynamicImage::ImageRgba16(image)
+    }
+
+    fn to_unit(self) -> f32 {
+        self as f32 / 65535.0
+    }
+
+    fn from_unit(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * 65535.0).round() as u16
+    }
+
+    fn max_f32() -> f32 {
+        65535.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round() as u16
+    }
+
+    fn as_f32(self) -> f32 {
+        self as f32
+    }
+}
+
+impl Depth for f32 {
+    type Rgba = Rgba<f32>;
+    type Rgb = Rgb<f32>;
+
+    fn from_dynamic(image: DynamicImage) -> RgbaBuffer<Self> {
+        image.into_rgba32f()
+    }
+
+    fn into_dynamic(image: RgbaBuffer<Self>) -> DynamicImage {
+        DynamicImage::ImageRgba32F(image)
+    }
+
+    fn to_unit(self) -> f32 {
+        self
+    }
+
+    fn from_unit(value: f32) -> Self {
+        value.clamp(0.0, 1.0)
+    }
+
+    fn max_f32() -> f32 {
+        1.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn as_f32(self) -> f32 {
+        self
+    }
+}
+
+pub fn rgba<T: Depth>(channels: [T; 4]) -> T::Rgba {
+    *T::Rgba::from_slice(&channels)
+}
+
+pub fn rgb<T: Depth>(channels: [T; 3]) -> T::Rgb {
+    *T::Rgb::from_slice(&channels)
+}
+
+fn dither<T: Depth>(image: &RgbBuffer<T>) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output = RgbImage::new(width, height);
+
+    // Errors carried to the current and the next row
+    let mut current = vec![[0f32; 3]; width as usize + 2];
+    let mut next = vec![[0f32; 3]; width as usize + 2];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = x as usize + 1;
+            let source = image.get_pixel(x, y).channels();
+            let mut quantised = [0u8; 3];
+
+            for c in 0..3 {
+                let value = source[c].to_unit() * 255.0 + current[i][c];
+                let rounded = value.round().clamp(0.0, 255.0);
+                let error = value - rounded;
+
+                quantised[c] = rounded as u8;
+                current[i + 1][c] += error * 7.0 / 16.0;
+                next[i - 1][c] += error * 3.0 / 16.0;
+                next[i][c] += error * 5.0 / 16.0;
+                next[i + 1][c] += error / 16.0;
+            }
+
+            output.put_pixel(x, y, Rgb(quantised));
+        }
+
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|error| *error = [0.0; 3]);
+    }
+
+    output
+}
+
+/// Picks the channel type matching the decoded image
+pub enum SourceDepth {
+    Eight,
+    Sixteen,
+    Float,
+}
+
+impl SourceDepth {
+    pub fn of(image: &DynamicImage) -> Self {
+        match image {
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => Self::Sixteen,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => Self::Float,
+            _ => Self::Eight,
+        }
+    }
+}
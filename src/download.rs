@@ -0,0 +1,200 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use ureq::{Agent, AgentBuilder, ErrorKind};
+
+use crate::image_reader::MEGABYTE;
+use crate::{status, warning};
+
+/// Numbers the download dirs of one process
+static DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// Bytes read from the start of a download to recognise the image format
+const MAGIC_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadSettings {
+    pub timeout: Duration,
+    pub retries: u32,
+    /// Size limit in bytes
+    pub max_size: u64,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            retries: 2,
+            max_size: 100 * MEGABYTE,
+        }
+    }
+}
+
+/// Downloads images into a temporary directory that is removed when dropped
+pub struct Downloads {
+    agent: Agent,
+    settings: DownloadSettings,
+    dir: PathBuf,
+    count: usize,
+}
+
+impl Downloads {
+    pub fn new(settings: DownloadSettings) -> Self {
+        Self {
+            agent: AgentBuilder::new().timeout(settings.timeout).build(),
+            settings,
+            dir: std::env::temp_dir().join(format!(
+                "imoyo-{}-{}",
+                std::process::id(),
+                DIRS.fetch_add(1, Ordering::Relaxed)
+            )),
+            count: 0,
+        }
+    }
+
+    /// Every download gets its own folder, so equal names from different URLs don't clash
+    pub fn fetch(&mut self, url: &str) -> Result<String, String> {
+        let dir = self.dir.join(self.count.to_string());
+        self.count += 1;
+
+        fs::create_dir_all(&dir)
+            .map_err(|err| format!("Failed to create download dir {dir:?}: {err}"))?;
+
+        let path = dir.join(file_name(url));
+        let path_name = path
+            .to_str()
+            .ok_or_else(|| format!("Invalid unicode in download path {path:?}"))?
+            .to_string();
+
+        status!("Downloading image {url}");
+
+        let mut attempt = 0;
+
+        loop {
+            match self.download(url, &path) {
+                Ok(()) => return Ok(path_name),
+                Err(Failure::Retry(message)) if attempt < self.settings.retries => {
+                    attempt += 1;
+                    warning!("{message}, retrying ({attempt}/{})", self.settings.retries);
+                    thread::sleep(Duration::from_millis(500 * attempt as u64));
+                }
+                Err(Failure::Retry(message) | Failure::Fatal(message)) => {
+                    let _ = fs::remove_dir_all(&dir);
+                    return Err(format!("Failed to download image {url}: {message}"));
+                }
+            }
+        }
+    }
+
+    /// Deletes a downloaded file together with its folder
+    pub fn remove(&self, path: &str) {
+        if let Some(dir) = Path::new(path)
+            .parent()
+            .filter(|dir| dir.starts_with(&self.dir))
+        {
+            if let Err(err) = fs::remove_dir_all(dir) {
+                warning!("Failed to remove download {path}: {err}");
+            }
+        }
+    }
+
+    /// Streams the response into `path` and checks it really is an image
+    fn download(&self, url: &str, path: &PathBuf) -> Result<(), Failure> {
+        let response = match self.agent.get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => {
+                return Err(Failure::Retry(format!("HTTP status {status}")))
+            }
+            Err(ureq::Error::Status(status, _)) => {
+                return Err(Failure::Fatal(format!("HTTP status {status}")))
+            }
+            // Retrying can't fix a request that is wrong in itself
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::InvalidUrl
+                        | ErrorKind::UnknownScheme
+                        | ErrorKind::InsecureRequestHttpsOnly
+                        | ErrorKind::TooManyRedirects
+                        | ErrorKind::InvalidProxyUrl
+                        | ErrorKind::ProxyUnauthorized
+                ) =>
+            {
+                return Err(Failure::Fatal(err.to_string()))
+            }
+            Err(err) => return Err(Failure::Retry(err.to_string())),
+        };
+
+        if let Some(content_type) = response.header("Content-Type") {
+            let mime = content_type.split(';').next().unwrap_or("").trim();
+
+            if !mime.starts_with("image/") && mime != "application/octet-stream" {
+                return Err(Failure::Fatal(format!("Unexpected content type {mime}")));
+            }
+        }
+
+        let max_size = self.settings.max_size;
+
+        if let Some(length) = response
+            .header("Content-Length")
+            .and_then(|length| length.parse::<u64>().ok())
+        {
+            if length > max_size {
+                return Err(Failure::Fatal(size_error(max_size)));
+            }
+        }
+
+        let mut file = File::create(path)
+            .map_err(|err| Failure::Fatal(format!("Failed to create {path:?}: {err}")))?;
+
+        // One byte over the limit tells an oversized body from one that just fits
+        let written = io::copy(&mut response.into_reader().take(max_size + 1), &mut file)
+            .map_err(|err| Failure::Retry(err.to_string()))?;
+
+        if written > max_size {
+            return Err(Failure::Fatal(size_error(max_size)));
+        }
+
+        let mut magic = Vec::with_capacity(MAGIC_BYTES);
+        File::open(path)
+            .and_then(|file| file.take(MAGIC_BYTES as u64).read_to_end(&mut magic))
+            .map_err(|err| Failure::Fatal(format!("Failed to read {path:?}: {err}")))?;
+
+        image::guess_format(&magic)
+            .map(|_| ())
+            .map_err(|_| Failure::Fatal(String::from("Response is not a known image format")))
+    }
+}
+
+impl Drop for Downloads {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+enum Failure {
+    Retry(String),
+    Fatal(String),
+}
+
+/// Last path segment of the URL, `image` when the URL has none
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+
+    path.split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or("image")
+        .to_string()
+}
+
+fn size_error(max_size: u64) -> String {
+    format!("Image is larger than {} MB", max_size / MEGABYTE)
+}
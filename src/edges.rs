@@ -0,0 +1,45 @@
+use image::{GrayImage, Luma};
+use imageproc::{contrast, edges, filter, gradients};
+
+use crate::args::EdgeDetectionSettings;
+
+// Same sigma `imageproc::edges::canny` blurs with before computing gradients
+const CANNY_SIGMA: f32 = 1.4;
+
+pub fn canny(image: &GrayImage, settings: &EdgeDetectionSettings) -> (GrayImage, f32, f32) {
+    let blurred = settings
+        .blur_sigma
+        .map(|sigma| filter::gaussian_blur_f32(image, sigma));
+    let image = blurred.as_ref().unwrap_or(image);
+
+    let (low_threshold, high_threshold) = if settings.auto {
+        auto_thresholds(image)
+    } else {
+        (settings.low_threshold, settings.high_threshold)
+    };
+
+    (
+        edges::canny(image, low_threshold, high_threshold),
+        low_threshold,
+        high_threshold,
+    )
+}
+
+/// Otsu level of the gradient magnitude is used as the high threshold, half of it as the low one
+fn auto_thresholds(image: &GrayImage) -> (f32, f32) {
+    let blurred = filter::gaussian_blur_f32(image, CANNY_SIGMA);
+    let magnitudes = gradients::sobel_gradients(&blurred);
+
+    let max = magnitudes.pixels().map(|p| p.0[0]).max().unwrap_or(0);
+    if max == 0 {
+        return (1.0, 10.0);
+    }
+
+    let scaled = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        Luma([(magnitudes.get_pixel(x, y).0[0] as f32 / max as f32 * 255.0) as u8])
+    });
+
+    let high_threshold = (contrast::otsu_level(&scaled) as f32 / 255.0 * max as f32).max(1.0);
+
+    (high_threshold / 2.0, high_threshold)
+}
@@ -0,0 +1,135 @@
+use image::metadata::Orientation;
+
+use crate::warning;
+
+const IMAGE_DESCRIPTION: u16 = 0x010E;
+const ARTIST: u16 = 0x013B;
+const COPYRIGHT: u16 = 0x8298;
+
+const ASCII: u16 = 2;
+
+/// Which EXIF fields are written to the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetadataPolicy {
+    StripAll,
+    KeepCopyright,
+    KeepAll,
+}
+
+/// Orientation is already applied to pixels on load, so it is reset in kept metadata
+pub fn output_exif(exif: Option<Vec<u8>>, policy: MetadataPolicy) -> Option<Vec<u8>> {
+    match policy {
+        MetadataPolicy::StripAll => None,
+        MetadataPolicy::KeepCopyright => exif.and_then(|exif| {
+            copyright_fields(&exif).unwrap_or_else(|message| {
+                warning!("{message}, EXIF is dropped");
+                None
+            })
+        }),
+        MetadataPolicy::KeepAll => exif.map(|mut exif| {
+            let _ = Orientation::remove_from_exif_chunk(&mut exif);
+            exif
+        }),
+    }
+}
+
+/// Builds a new little endian EXIF chunk holding only description, artist and copyright from
+/// IFD0, `None` when it has none of them
+pub fn copyright_fields(exif: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let little_endian = match exif.get(..4) {
+        Some([0x49, 0x49, 42, 0]) => true,
+        Some([0x4d, 0x4d, 0, 42]) => false,
+        _ => return Err(String::from("Invalid EXIF header")),
+    };
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = [*exif.get(offset)?, *exif.get(offset + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = exif.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let truncated = || String::from("Truncated EXIF");
+
+    let ifd = read_u32(4).ok_or_else(truncated)? as usize;
+    let entries =
+        read_u16(ifd).ok_or_else(|| format!("EXIF IFD0 at {ifd} is outside the data"))? as usize;
+
+    let mut fields: Vec<(u16, &[u8])> = Vec::new();
+
+    for i in 0..entries {
+        let entry = ifd + 2 + i * 12;
+        let tag = read_u16(entry).ok_or_else(truncated)?;
+
+        if ![IMAGE_DESCRIPTION, ARTIST, COPYRIGHT].contains(&tag)
+            || read_u16(entry + 2).ok_or_else(truncated)? != ASCII
+        {
+            continue;
+        }
+
+        let count = read_u32(entry + 4).ok_or_else(truncated)? as usize;
+        let value = if count <= 4 {
+            exif.get(entry + 8..entry + 8 + count)
+        } else {
+            let offset = read_u32(entry + 8).ok_or_else(truncated)? as usize;
+            exif.get(offset..offset + count)
+        }
+        .ok_or_else(|| format!("EXIF value of tag {tag:#06x} is outside the data"))?;
+
+        fields.push((tag, value));
+    }
+
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    fields.sort_by_key(|(tag, _)| *tag);
+
+    Ok(Some(write_ascii_fields(&fields)))
+}
+
+fn write_ascii_fields(fields: &[(u16, &[u8])]) -> Vec<u8> {
+    let ifd_size = 2 + fields.len() * 12 + 4;
+    let mut data_offset = 8 + ifd_size;
+
+    let mut chunk = vec![0x49, 0x49, 42, 0];
+    chunk.extend_from_slice(&8u32.to_le_bytes());
+    chunk.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+
+    let mut data: Vec<u8> = Vec::new();
+
+    for (tag, value) in fields {
+        chunk.extend_from_slice(&tag.to_le_bytes());
+        chunk.extend_from_slice(&ASCII.to_le_bytes());
+        chunk.extend_from_slice(&(value.len() as u32).to_le_bytes());
+
+        if value.len() <= 4 {
+            let mut inline = [0u8; 4];
+            inline[..value.len()].copy_from_slice(value);
+            chunk.extend_from_slice(&inline);
+        } else {
+            chunk.extend_from_slice(&(data_offset as u32).to_le_bytes());
+            data.extend_from_slice(value);
+            // Values start on word boundary
+            if value.len() % 2 == 1 {
+                data.push(0);
+            }
+            data_offset = 8 + ifd_size + data.len();
+        }
+    }
+
+    chunk.extend_from_slice(&0u32.to_le_bytes());
+    chunk.extend_from_slice(&data);
+
+    chunk
+}
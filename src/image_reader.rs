@@ -1,38 +1,203 @@
-use std::{fs::File, io::BufReader};
+use std::{fs::File, io::BufRead, io::BufReader, io::Cursor, io::Read, io::Seek};
 
-use image::{io::Reader, DynamicImage};
+use image::{DynamicImage, ImageDecoder, ImageError, ImageReader, ImageResult, Limits};
+
+use crate::color::{self, IccMode};
+use crate::memory::{Budget, Header, Plan, Strips};
+use crate::warning;
+
+pub const MEGABYTE: u64 = 1024 * 1024;
+
+/// Metadata carried from the source image to the output
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub icc_profile: Option<Vec<u8>>,
+    pub exif: Option<Vec<u8>>,
+}
 
 pub struct ImageProperties<T: From<DynamicImage>> {
     pub name: String,
     pub image: T,
+    pub metadata: Metadata,
+    /// Set when the image only fits into `--max-memory` if it is cropped in strips
+    pub strips: Option<Strips>,
 }
 
 impl<T: From<DynamicImage>> ImageProperties<T> {
-    pub fn read(image_path: &str) -> Result<ImageProperties<T>, String> {
-        let name = Self::read_image_name(image_path)?;
-        let image: T = Self::read_image(image_path)?.into();
+    pub fn read(
+        image_path: &str,
+        icc_mode: IccMode,
+        budget: Option<&Budget>,
+    ) -> Result<ImageProperties<T>, String> {
+        let name = image_name(image_path)?;
 
-        Ok(ImageProperties { name, image })
+        let strips = match budget {
+            Some(budget) => Self::plan(
+                ImageReader::new(BufReader::new(
+                    File::open(image_path)
+                        .map_err(|err| format!("Failed to open {image_path}: {err}"))?,
+                )),
+                image_path,
+                icc_mode,
+                budget,
+            )?,
+            None => None,
+        };
+
+        let decoded = Self::read_image(image_path, budget)?;
+
+        Self::from_decoded(name, decoded, icc_mode, image_path, strips)
     }
-    fn read_image_name(image_path: &str) -> Result<String, String> {
-        std::path::Path::new(image_path)
-            .file_stem()
-            .ok_or_else(|| format!("Invalid unicode for {image_path}"))?
-            .to_str()
-            .map_or(Err(format!("Invalid unicode for {image_path}")), |name| {
-                Ok(name.to_string())
-            })
+
+    /// Reads an image of any format from a stream such as stdin, `name` is used for the output
+    pub fn read_from<R: Read>(
+        mut reader: R,
+        name: &str,
+        icc_mode: IccMode,
+        budget: Option<&Budget>,
+    ) -> Result<ImageProperties<T>, String> {
+        // Decoders need to seek, so the stream is buffered completely
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|err| format!("Failed to read {name}: {err}"))?;
+
+        let strips = match budget {
+            Some(budget) => {
+                Self::plan(ImageReader::new(Cursor::new(&data)), name, icc_mode, budget)?
+            }
+            None => None,
+        };
+
+        let decoded = ImageReader::new(Cursor::new(&data))
+            .with_guessed_format()
+            .map_err(ImageError::from)
+            .and_then(|reader| Self::decode(reader, budget))
+            .map_err(|err| format!("Failed to decode {name}: {err}"))?;
+
+        Self::from_decoded(name.to_string(), decoded, icc_mode, name, strips)
     }
 
-    fn read_image(image_path: &str) -> Result<DynamicImage, String> {
-        Reader::open(image_path)
+    fn from_decoded(
+        name: String,
+        (image, mut metadata): (DynamicImage, Metadata),
+        icc_mode: IccMode,
+        source: &str,
+        mut strips: Option<Strips>,
+    ) -> Result<ImageProperties<T>, String> {
+        let image = match (icc_mode, metadata.icc_profile.take()) {
+            (IccMode::Convert, Some(icc_profile)) => {
+                // Images cropped in strips are converted strip by strip, a corner tries the profile
+                let sample = strips
+                    .is_some()
+                    .then(|| image.crop_imm(0, 0, image.width().min(1), image.height().min(1)));
+
+                match color::convert_to_srgb(sample.as_ref().unwrap_or(&image), &icc_profile) {
+                    Ok(converted) => match &mut strips {
+                        Some(strips) => {
+                            strips.icc_profile = Some(icc_profile);
+                            image
+                        }
+                        None => converted,
+                    },
+                    Err(err) => {
+                        warning!("{err} in {source}, colours are kept as they are");
+                        image
+                    }
+                }
+            }
+            (_, icc_profile) => {
+                metadata.icc_profile = icc_profile;
+                image
+            }
+        };
+
+        Ok(ImageProperties {
+            name,
+            image: image.into(),
+            metadata,
+            strips,
+        })
+    }
+
+    /// Estimates peak memory from the image header before any pixels are decoded
+    fn plan<R: BufRead + Seek>(
+        reader: ImageReader<R>,
+        image_path: &str,
+        icc_mode: IccMode,
+        budget: &Budget,
+    ) -> Result<Option<Strips>, String> {
+        let mut decoder = reader
+            .with_guessed_format()
             .map_err(|err| format!("Failed to open {image_path}: {err}"))?
-            .decode()
-            .or_else(|_| {
-                Reader::new(BufReader::new(File::open(image_path)?))
-                    .with_guessed_format()?
-                    .decode()
-            })
-            .map_err(|err| format!("Failed to decode {image_path}: {err}"))
+            .into_decoder()
+            .map_err(|err| format!("Failed to decode {image_path}: {err}"))?;
+
+        let header = Header::read(&mut decoder, icc_mode == IccMode::Convert)
+            .map_err(|err| format!("Failed to decode {image_path}: {err}"))?;
+
+        match budget.plan(&header) {
+            Ok(Plan::Full) => Ok(None),
+            Ok(Plan::Strips) => Ok(Some(Strips {
+                header,
+                budget: *budget,
+                icc_profile: None,
+            })),
+            Err(message) => Err(format!("Skipping {image_path}: {message}")),
+        }
+    }
+
+    fn read_image(
+        image_path: &str,
+        budget: Option<&Budget>,
+    ) -> Result<(DynamicImage, Metadata), String> {
+        Self::decode(
+            ImageReader::open(image_path)
+                .map_err(|err| format!("Failed to open {image_path}: {err}"))?,
+            budget,
+        )
+        .or_else(|_| {
+            Self::decode(
+                ImageReader::new(BufReader::new(File::open(image_path)?)).with_guessed_format()?,
+                budget,
+            )
+        })
+        .map_err(|err| format!("Failed to decode {image_path}: {err}"))
+    }
+
+    /// Decodes the image with EXIF orientation applied
+    fn decode<R: BufRead + Seek>(
+        mut reader: ImageReader<R>,
+        budget: Option<&Budget>,
+    ) -> ImageResult<(DynamicImage, Metadata)> {
+        // Caps each single allocation of the decoder, the total is estimated by the budget
+        if let Some(budget) = budget {
+            let mut limits = Limits::default();
+            limits.max_alloc = Some(budget.max());
+            reader.limits(limits);
+        }
+
+        let mut decoder = reader.into_decoder()?;
+        let metadata = Metadata {
+            icc_profile: decoder.icc_profile()?,
+            exif: decoder.exif_metadata()?,
+        };
+        let orientation = decoder.orientation()?;
+
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+
+        Ok((image, metadata))
     }
 }
+
+/// Output name of an image file, its file name without extension
+pub fn image_name(image_path: &str) -> Result<String, String> {
+    std::path::Path::new(image_path)
+        .file_stem()
+        .ok_or_else(|| format!("Invalid unicode for {image_path}"))?
+        .to_str()
+        .map_or(Err(format!("Invalid unicode for {image_path}")), |name| {
+            Ok(name.to_string())
+        })
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+
+use crate::image_reader::Metadata;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    /// Lossless WebP
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+}
+
+pub fn save(
+    image: &RgbImage,
+    path: &str,
+    format: OutputFormat,
+    metadata: &Metadata,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("Failed to create {path}: {err}"))?;
+    let mut writer = BufWriter::new(file);
+
+    write(image, &mut writer, format, metadata, path)?;
+
+    writer
+        .flush()
+        .map_err(|err| format!("Failed to write {path}: {err}"))
+}
+
+/// Encodes the image into any writer, `target` names it in error messages
+pub fn write<W: Write>(
+    image: &RgbImage,
+    writer: W,
+    format: OutputFormat,
+    metadata: &Metadata,
+    target: &str,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Jpeg => encode(JpegEncoder::new(writer), image, metadata, target),
+        OutputFormat::Png => encode(PngEncoder::new(writer), image, metadata, target),
+        OutputFormat::Webp => encode(WebPEncoder::new_lossless(writer), image, metadata, target),
+    }
+}
+
+fn encode<E: ImageEncoder>(
+    mut encoder: E,
+    image: &RgbImage,
+    metadata: &Metadata,
+    target: &str,
+) -> Result<(), String> {
+    if let Some(icc_profile) = &metadata.icc_profile {
+        encoder
+            .set_icc_profile(icc_profile.clone())
+            .map_err(|err| format!("Failed to embed ICC profile into {target}: {err}"))?;
+    }
+
+    if let Some(exif) = &metadata.exif {
+        encoder
+            .set_exif_metadata(exif.clone())
+            .map_err(|err| format!("Failed to embed EXIF into {target}: {err}"))?;
+    }
+
+    encoder
+        .write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgb8,
+        )
+        .map_err(|err| format!("Failed to encode {target}: {err}"))
+}
@@ -0,0 +1,109 @@
+use std::fs;
+use std::io::{self, Read};
+
+/// Header names recognised in CSV input lists
+const SOURCE_COLUMNS: [&str; 4] = ["url", "path", "input", "source"];
+const NAME_COLUMNS: [&str; 4] = ["name", "sku", "output", "output_name"];
+
+/// Image path or URL with an optional output name overriding the file name
+#[derive(Debug)]
+pub struct Input {
+    pub source: String,
+    pub name: Option<String>,
+}
+
+impl Input {
+    pub fn new(source: String) -> Self {
+        Self { source, name: None }
+    }
+}
+
+/// Reads one path or URL per line from a file or from stdin for `-`.
+///
+/// Empty lines and lines starting with `#` are skipped. A first line naming a
+/// `url`/`path` column makes the list a CSV whose `name`/`sku` column sets the output name.
+pub fn read_list(list: &str) -> Result<Vec<Input>, String> {
+    let content = if list == "-" {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|err| format!("Failed to read input list from stdin: {err}"))?;
+        content
+    } else {
+        fs::read_to_string(list)
+            .map_err(|err| format!("Failed to read input list {list}: {err}"))?
+    };
+
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let header = lines.peek().map(|line| {
+        split_csv(line)
+            .iter()
+            .map(|column| column.to_lowercase())
+            .collect::<Vec<String>>()
+    });
+    let find = |names: &[&str]| {
+        header.as_ref().and_then(|header| {
+            header
+                .iter()
+                .position(|column| names.contains(&column.as_str()))
+        })
+    };
+
+    let Some(source_column) = find(&SOURCE_COLUMNS) else {
+        return Ok(lines.map(|line| Input::new(line.to_string())).collect());
+    };
+    let name_column = find(&NAME_COLUMNS);
+
+    Ok(lines
+        .skip(1)
+        .filter_map(|line| {
+            let fields = split_csv(line);
+            let source = fields.get(source_column)?.trim().to_string();
+
+            (!source.is_empty()).then(|| Input {
+                source,
+                name: name_column
+                    .and_then(|column| fields.get(column))
+                    .map(|name| name.trim().replace(['/', '\\'], "_"))
+                    .filter(|name| !name.is_empty()),
+            })
+        })
+        .collect())
+}
+
+pub fn is_image(image_path: &str) -> bool {
+    image_path.ends_with(".jpg")
+        || image_path.ends_with(".png")
+        || image_path.ends_with(".jpeg")
+        || image_path.ends_with(".webp")
+        || image_path.ends_with(".tif")
+        || image_path.ends_with(".avif")
+}
+
+/// Splits a CSV line on commas outside of double quotes
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    fields.push(field);
+    fields
+}
@@ -0,0 +1,22 @@
+pub mod args;
+pub mod background;
+pub mod batch;
+pub mod cache;
+pub mod color;
+pub mod crop;
+pub mod debug;
+pub mod depth;
+pub mod download;
+pub mod edges;
+pub mod exif;
+pub mod image_reader;
+pub mod image_writer;
+pub mod input;
+pub mod log;
+pub mod memory;
+pub mod pipeline;
+pub mod report;
+pub mod scaler;
+pub mod server;
+pub mod shadow;
+pub mod watch;
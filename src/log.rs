@@ -0,0 +1,156 @@
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Progress bar on the last line of stderr, empty when there is none
+static PROGRESS: Mutex<String> = Mutex::new(String::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    /// `-v`
+    Verbose,
+    /// `-vv`
+    Debug,
+}
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Verbose => "verbose",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Sets the most detailed level that is printed and the message format
+pub fn init(level: Level, format: LogFormat) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+/// Moves status messages to stderr, used when stdout carries the output image
+pub fn use_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Errors and warnings go to stderr, other messages to stdout unless it carries the image
+pub fn print(level: Level, message: Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    if is_json() {
+        write(
+            level,
+            &json!({ "level": level.name(), "message": message.to_string() }),
+        );
+    } else {
+        write(level, &message);
+    }
+}
+
+/// Structured record for the JSON log format, ignored for text logs
+pub fn record(level: Level, mut fields: Value) {
+    if !enabled(level) || !is_json() {
+        return;
+    }
+
+    if let Some(fields) = fields.as_object_mut() {
+        fields.insert(String::from("level"), Value::from(level.name()));
+    }
+
+    write(level, &fields);
+}
+
+fn write(level: Level, line: &dyn std::fmt::Display) {
+    let progress = PROGRESS.lock().unwrap_or_else(|err| err.into_inner());
+
+    // Messages are printed above the progress bar, which is drawn again below them
+    if !progress.is_empty() {
+        eprint!("\r\x1b[2K");
+    }
+
+    if level <= Level::Warn || TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
+
+    if !progress.is_empty() {
+        eprint!("{progress}");
+    }
+}
+
+/// Replaces the progress bar, an empty line removes it
+pub fn progress(line: String) {
+    let mut progress = PROGRESS.lock().unwrap_or_else(|err| err.into_inner());
+
+    eprint!("\r\x1b[2K{line}");
+    *progress = line;
+}
+
+/// `println!` for errors, printed even with `-q`
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Error, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+/// `println!` for status messages
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+/// Status messages printed with `-v`
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Verbose, format_args!($($arg)*))
+    };
+}
+
+/// Status messages printed with `-vv`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
@@ -1,173 +1,295 @@
-mod args;
-mod background;
-mod crop;
-mod image_reader;
-mod scaler;
-
-use background::Background;
-use crop::Crop;
+use imoyo::args::{self, Args};
+use imoyo::batch::{Batch, ImageResult, Status};
+use imoyo::cache::{self, Cache};
+use imoyo::download::Downloads;
+use imoyo::image_reader::{self, ImageProperties};
+use imoyo::input::{self, Input};
+use imoyo::log::{self, Level, LogFormat};
+use imoyo::memory::Budget;
+use imoyo::pipeline::{self, Outcome};
+use imoyo::report::{self, ImageReport};
+use imoyo::{debug, error, exif, image_writer, status, warning};
+use imoyo::{server, watch};
 
 use image::DynamicImage;
-use image_reader::ImageProperties;
-use std::fs::File;
-use std::io::Write;
+use std::io::{self, IsTerminal};
 use std::path::Path;
+use std::time::Instant;
+
+/// Image argument that reads from stdin and writes the result to stdout
+const STDIN: &str = "-";
 
 fn main() {
+    let argv = std::env::args().collect::<Vec<String>>();
+
+    if argv.get(1).map(String::as_str) == Some("serve") {
+        let (settings, defaults) = Args::serve_settings(&argv[1..]);
+
+        let args = Args::parse(&[&argv[..1], &defaults].concat());
+        log::init(args.log_level, args.log_format);
+
+        if let Err(message) = server::run(settings, defaults) {
+            error!("{message}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if argv.get(1).map(String::as_str) == Some("watch") {
+        let (settings, rest) = Args::watch_settings(&argv[1..]);
+
+        let args = Args::parse(&rest);
+        log::init(args.log_level, args.log_format);
+
+        if let Err(message) = watch::run(settings, &args) {
+            error!("{message}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let args = args::Args::get();
+    if args.pipe {
+        log::use_stderr();
+    }
+    log::init(args.log_level, args.log_format);
+    let mut reports: Vec<ImageReport> = Vec::new();
 
-    for image_path in get_image_paths(&args.ignored) {
-        let image_properties = match ImageProperties::read(&image_path) {
-            Ok(props) => props,
-            Err(message) => {
-                println!("{message}");
-                continue;
+    let mut downloads = Downloads::new(args.download);
+
+    // Piped and analyzed images leave no output file the cache could point to
+    let mut cache =
+        (!args.pipe && args.analyze.is_none()).then(|| Cache::open(&args.settings_key()));
+
+    let mut batch = Batch::new(
+        !args.pipe
+            && io::stderr().is_terminal()
+            && args.log_format == LogFormat::Text
+            && log::enabled(Level::Info),
+    );
+    let inputs = get_inputs(&args, &mut batch);
+
+    batch.expect(inputs.len());
+
+    for input in inputs {
+        let start = Instant::now();
+
+        // Links are downloaded one at a time and removed once their image is done
+        let mut result = if input.source.starts_with("http") {
+            match downloads.fetch(&input.source) {
+                Ok(path) => {
+                    let result = process(&args, &input, &path, &mut cache, &mut reports);
+                    downloads.remove(&path);
+                    result
+                }
+                Err(message) => {
+                    error!("{message}");
+                    ImageResult::failed(&input.source, message)
+                }
             }
+        } else {
+            process(&args, &input, &input.source, &mut cache, &mut reports)
         };
 
-        let image_name = image_properties.name;
-        let mut image = image_properties.image;
+        result.time = start.elapsed();
+        batch.record(result);
+    }
 
-        let mut path = String::new();
+    batch.finish();
 
-        let crop = Crop::new(args.padding, Background::white());
+    if let Some(format) = args.analyze {
+        report::print(format, &reports);
+    } else if batch.count() > 1 || log::enabled(Level::Verbose) {
+        batch.print_summary();
+    }
 
-        if let Some(alpha_filter) = args.alpha_filter {
-            if args.verbose {
-                println!("Applying alpha filter {alpha_filter} to image {image_name}");
-            }
-            image = background::filter_alpha(&image, alpha_filter);
-            path.push_str("-a");
+    if let Some(path) = &args.report {
+        if let Err(message) = batch.write_report(path) {
+            error!("{message}");
         }
+    }
 
-        if args.crop {
-            if args.verbose {
-                println!("Cropping image {image_name}");
-            }
-            image = crop.crop_to_object(&image);
-            path.push_str("-c");
-        }
+    if batch.any_failed() {
+        std::process::exit(1);
+    }
+}
 
-        if args.square {
-            if args.verbose {
-                println!("Cropping image {image_name} to square");
-            }
-            image = crop.fill_to_square(&image);
-            path.push_str("-s");
-        }
+/// Reads, processes and saves one image, `image_path` is the local file of the input
+fn process(
+    args: &Args,
+    input: &Input,
+    image_path: &str,
+    cache: &mut Option<Cache>,
+    reports: &mut Vec<ImageReport>,
+) -> ImageResult {
+    let mut result = ImageResult::new(&input.source);
+    let budget = Budget::new(args);
+    let budget = budget.as_ref();
 
-        if let Some(scaler) = &args.scaler {
-            if args.verbose {
-                println!("Resizing image {image_name}");
+    let cached = match cache
+        .as_ref()
+        .zip(cache_key(image_path, input.name.as_deref()))
+    {
+        Some((cache, (image_name, input_hash))) => {
+            if let Some(output) = cache
+                .output(&image_name, &input_hash)
+                .filter(|_| !args.force)
+            {
+                status!("Skipping image {image_name}: {output} is up to date");
+                result.status = Status::Skipped(format!("{output} is up to date"));
+                result.output = Some(output.to_string());
+                return result;
             }
-            image = scaler.resize(DynamicImage::ImageRgba8(image)).to_rgba8();
-            path.push_str("-r");
+            Some((image_name, input_hash))
         }
+        None => None,
+    };
 
-        if args.edge_detection.in_use {
-            if args.verbose {
-                println!("Detecting edges in image {image_name}");
-            }
-            image = crop.crop_to_edges_canny(
-                &image,
-                args.edge_detection.low_threshold,
-                args.edge_detection.high_threshold,
-                args.verbose,
-            );
-            path.push_str("-e");
+    let read = if image_path == STDIN {
+        ImageProperties::read_from(io::stdin(), "stdin", args.icc_mode, budget)
+    } else {
+        ImageProperties::read(image_path, args.icc_mode, budget)
+    };
+
+    let image_properties: ImageProperties<DynamicImage> = match read {
+        Ok(props) => props,
+        Err(message) => {
+            error!("{message}");
+            result.status = Status::Failed(message);
+            return result;
         }
+    };
 
-        if !path.is_empty() {
-            let bg = args
-                .background
-                .map_or(Background::white(), Background::from_rgb);
+    let image_name = input.name.clone().unwrap_or(image_properties.name);
+    let image = image_properties.image;
+    let strips = image_properties.strips;
+    let mut metadata = image_properties.metadata;
+    metadata.exif = exif::output_exif(metadata.exif, args.metadata_policy);
 
-            if args.verbose {
-                println!("Setting background {:?}", bg.color);
-            }
+    result.source_size = Some((image.width(), image.height()));
+
+    debug!(
+        "Read image {image_path}: {}x{} {:?}",
+        image.width(),
+        image.height(),
+        image.color()
+    );
 
-            let rgb_image = bg.set_background(&image);
+    match pipeline::run(args, &input.source, &image_name, image, strips) {
+        Outcome::Processed(rgb_image, path) => {
+            let output = (image_path != STDIN)
+                .then(|| pipeline::output_file(&image_name, &path, args.format));
 
-            rgb_image
-                .save(image_name.to_string() + &path + "-processed.jpg")
-                .unwrap_or_else(|err| panic!("Failed to save image {image_name}: {err}"));
+            let saved = match &output {
+                None => image_writer::write(
+                    &rgb_image,
+                    io::stdout().lock(),
+                    args.format,
+                    &metadata,
+                    "stdout",
+                ),
+                Some(output) => image_writer::save(&rgb_image, output, args.format, &metadata),
+            };
+
+            if let Err(err) = saved {
+                let message = format!("Failed to save image {image_name}: {err}");
+                error!("{message}");
+                result.status = Status::Failed(message);
+                return result;
+            }
 
-            println!("Processed image {image_name} {:?}", image.dimensions());
+            if let Some(((cache, (cache_name, input_hash)), output)) =
+                cache.as_mut().zip(cached).zip(output.as_ref())
+            {
+                cache.insert(&cache_name, &input_hash, output);
+            }
+
+            status!("Processed image {image_name} {:?}", rgb_image.dimensions());
+            result.output_size = Some(rgb_image.dimensions());
+            result.output = output;
+        }
+        Outcome::Analyzed(report) => reports.push(report),
+        Outcome::Skipped(message) => {
+            warning!("Skipping image {image_name}: {message}");
+            result.status = Status::Skipped(message);
+        }
+        Outcome::Failed(message) => {
+            error!("Rejecting image {image_name}: {message}");
+            result.status = Status::Failed(message);
         }
+        Outcome::Unchanged => result.status = Status::Skipped(String::from("nothing to do")),
     }
+
+    result
 }
 
-fn get_image_paths(ignored: &[usize]) -> Vec<String> {
-    let mut paths: Vec<String> = Vec::new();
+/// Output name and content hash of an input file, `None` when it can't be cached
+fn cache_key(image_path: &str, name: Option<&str>) -> Option<(String, String)> {
+    if image_path == STDIN {
+        return None;
+    }
 
-    std::env::args()
-        .enumerate()
-        .filter(|(_, arg)| !arg.starts_with('-'))
-        .filter(|(i, _)| !ignored.contains(i))
-        .map(|(_, arg)| arg)
-        .for_each(|arg| {
-            if arg.starts_with("http") {
-                paths.push(download_image(&arg));
-            } else if Path::new(&arg).exists() {
-                let path = Path::new(&arg);
-
-                if path.is_dir() {
-                    paths.extend(
-                        path.read_dir()
-                            .unwrap_or_else(|err| panic!("Failed to read dir {arg}: {err}"))
-                            .map(|entry| {
-                                entry
-                                    .unwrap_or_else(|err| panic!("Failed to read dir entry: {err}"))
-                                    .path()
-                                    .to_str()
-                                    .unwrap_or_else(|| panic!("Invalid unicode in {arg} dir"))
-                                    .to_owned()
-                            })
-                            .filter(|name| is_image(name)),
-                    );
-                } else {
-                    paths.push(arg.to_owned());
-                }
-            } else {
-                println!("Can't find image {arg}");
-            }
-        });
+    let image_name = match name {
+        Some(name) => name.to_string(),
+        None => image_reader::image_name(image_path).ok()?,
+    };
 
-    paths
+    cache::hash_file(image_path)
+        .map(|input_hash| (image_name, input_hash))
+        .ok()
 }
 
-fn download_image(url: &str) -> String {
-    let name = url
-        .split('/')
-        .last()
-        .unwrap_or_else(|| panic!("Invalid image name for {url}"))
-        .split('?')
-        .next()
-        .unwrap_or_else(|| panic!("Invalid image name for {url}"));
-    let mut file =
-        File::create(name).unwrap_or_else(|_| panic!("Failed to create temp file {name}"));
-
-    let mut buf: Vec<u8> = Vec::new();
+/// Image arguments followed by the entries of `--input-list`, links are kept for the download
+fn get_inputs(args: &Args, batch: &mut Batch) -> Vec<Input> {
+    let mut sources: Vec<Input> = std::env::args()
+        .enumerate()
+        .filter(|(_, arg)| !arg.starts_with('-') || arg == STDIN)
+        .filter(|(i, _)| !args.ignored.contains(i))
+        .map(|(_, arg)| Input::new(arg))
+        .collect();
 
-    println!("Downloading image {name}");
+    if let Some(list) = &args.input_list {
+        match input::read_list(list) {
+            Ok(inputs) => sources.extend(inputs),
+            Err(message) => {
+                error!("{message}");
+                batch.record(ImageResult::failed(list, message));
+            }
+        }
+    }
 
-    let _ = ureq::get(url)
-        .call()
-        .unwrap_or_else(|err| panic!("Failed to download image {name}: {err}"))
-        .into_reader()
-        .read_to_end(&mut buf);
+    let mut inputs: Vec<Input> = Vec::new();
 
-    file.write_all(&buf)
-        .unwrap_or_else(|err| panic!("Failed to save image {name}: {err}"));
+    for Input { source, name } in sources {
+        if source == STDIN || source.starts_with("http") {
+            inputs.push(Input { source, name });
+        } else if Path::new(&source).exists() {
+            let path = Path::new(&source);
 
-    name.to_owned()
-}
+            if path.is_dir() {
+                inputs.extend(
+                    path.read_dir()
+                        .unwrap_or_else(|err| panic!("Failed to read dir {source}: {err}"))
+                        .map(|entry| {
+                            entry
+                                .unwrap_or_else(|err| panic!("Failed to read dir entry: {err}"))
+                                .path()
+                                .to_str()
+                                .unwrap_or_else(|| panic!("Invalid unicode in {source} dir"))
+                                .to_owned()
+                        })
+                        .filter(|name| input::is_image(name))
+                        .map(Input::new),
+                );
+            } else {
+                inputs.push(Input { source, name });
+            }
+        } else {
+            let message = format!("Can't find image {source}");
+            error!("{message}");
+            batch.record(ImageResult::failed(&source, message));
+        }
+    }
 
-fn is_image(image_path: &str) -> bool {
-    image_path.ends_with(".jpg")
-        || image_path.ends_with(".png")
-        || image_path.ends_with(".jpeg")
-        || image_path.ends_with(".webp")
-        || image_path.ends_with(".tif")
-        || image_path.ends_with(".avif")
+    inputs
 }
@@ -0,0 +1,249 @@
+use image::metadata::Orientation;
+use image::{ColorType, ImageDecoder, ImageResult};
+
+use crate::args::Args;
+use crate::image_reader::MEGABYTE;
+use crate::scaler::Scaler;
+use crate::shadow::ShadowMode;
+
+/// Rows converted to the working format at a time when an image is cropped in strips
+pub const STRIP_ROWS: u32 = 256;
+
+/// Shadow detection keeps luma, chroma and two masks per pixel
+const SHADOW_PIXEL_BYTES: u64 = 14;
+
+/// Chroma key keeps one `f32` matte per pixel
+const KEY_PIXEL_BYTES: u64 = 4;
+
+/// Resizing samples rows into an `f32` RGBA image first, in linear light also the source and
+/// the result are `f32` RGBA copies
+const RESIZE_PIXEL_BYTES: u64 = 16;
+
+/// Canny keeps a luma copy, its blur, two Sobel gradients, the gradient magnitudes before and
+/// after non-maximum suppression and the edge map
+const EDGE_PIXEL_BYTES: u64 = 20;
+
+/// Size and format of an image as read from its header before any pixel is decoded
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    pub width: u32,
+    pub height: u32,
+    color_type: ColorType,
+    /// EXIF orientation swaps or rotates, which copies the decoded image once
+    rotated: bool,
+    /// Embedded profile is converted to sRGB
+    icc: bool,
+}
+
+impl Header {
+    pub fn read(decoder: &mut impl ImageDecoder, convert_icc: bool) -> ImageResult<Self> {
+        let (width, height) = decoder.dimensions();
+        let orientation = decoder.orientation()?;
+
+        Ok(Self {
+            width,
+            height,
+            color_type: decoder.color_type(),
+            rotated: !matches!(
+                orientation,
+                Orientation::NoTransforms | Orientation::FlipHorizontal | Orientation::FlipVertical
+            ),
+            icc: convert_icc && decoder.icc_profile()?.is_some(),
+        })
+    }
+
+    fn decoded_bytes(&self) -> u64 {
+        self.width as u64 * self.height as u64 * self.color_type.bytes_per_pixel() as u64
+    }
+
+    /// Bytes per pixel of the RGBA image the pipeline works on, at the depth of the source
+    fn working_pixel_bytes(&self) -> u64 {
+        match self.color_type {
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => 8,
+            ColorType::Rgb32F | ColorType::Rgba32F => 16,
+            _ => 4,
+        }
+    }
+
+    /// Decoded rows cut out, converted to sRGB and to the working format, with the extra
+    /// neighbour rows
+    fn strip_bytes(&self) -> u64 {
+        let conversions = if self.icc { 3 } else { 1 };
+
+        self.width as u64
+            * (STRIP_ROWS as u64 + 2)
+            * (self.color_type.bytes_per_pixel() as u64 + conversions * self.working_pixel_bytes())
+    }
+}
+
+/// Way an image is loaded within `--max-memory`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Plan {
+    /// Whole image is converted to the working format and processed
+    Full,
+    /// Object is found in strips of `STRIP_ROWS` rows and only the crop region is converted
+    Strips,
+}
+
+/// Image too large for the full pipeline, it is decoded whole but cropped strip by strip
+pub struct Strips {
+    pub header: Header,
+    pub budget: Budget,
+    /// Profile that converts each strip to sRGB
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+/// Peak memory estimates of the configured steps against `--max-memory`
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    max: u64,
+    /// Only cropping touches all pixels before the crop, so the rest can run on the region
+    strips: bool,
+    key: bool,
+    /// `-c` and `--crop-to` copy the region out of the working image, padding alone copies it
+    /// onto a larger canvas
+    crop: bool,
+    shadow: bool,
+    /// Shadows are cleared on a copy of the image that is only used for detection
+    shadow_copy: bool,
+    square: bool,
+    scaler: Option<Scaler>,
+    edges: bool,
+}
+
+impl Budget {
+    /// `None` without `--max-memory`
+    pub fn new(args: &Args) -> Option<Self> {
+        let shadow = matches!(
+            args.shadow,
+            Some(ShadowMode::Exclude) | Some(ShadowMode::Remove)
+        );
+
+        args.max_memory.map(|max| Self {
+            max,
+            strips: args.crop
+                && args.key.is_none()
+                && !shadow
+                && args.debug_dir.is_none()
+                && args.analyze.is_none(),
+            key: args.key.is_some(),
+            crop: args.crop || args.crop_to.is_some() || !args.padding.is_empty(),
+            shadow,
+            shadow_copy: args.shadow == Some(ShadowMode::Exclude),
+            square: args.square,
+            scaler: args.scaler,
+            edges: args.edge_detection.in_use,
+        })
+    }
+
+    /// Limit for single allocations of the decoder
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Processes the image whole if it fits, in strips if the steps allow it
+    pub fn plan(&self, header: &Header) -> Result<Plan, String> {
+        let decoded = header.decoded_bytes();
+        let decode = decoded * if header.rotated { 2 } else { 1 };
+        let working = header.width as u64 * header.height as u64 * header.working_pixel_bytes();
+        let load = decoded + working * if header.icc { 2 } else { 1 };
+
+        let full = decode
+            .max(load)
+            .max(self.steps(header, header.width, header.height));
+
+        if full <= self.max {
+            return Ok(Plan::Full);
+        }
+
+        let needed = decode.max(decoded + header.strip_bytes());
+
+        if !self.strips || needed > self.max {
+            return Err(self.too_large(header.width, header.height, full));
+        }
+
+        Ok(Plan::Strips)
+    }
+
+    /// Checks the crop region found in strips before it is converted
+    pub fn check_region(&self, header: &Header, width: u32, height: u32) -> Result<(), String> {
+        let region = width as u64 * height as u64 * header.working_pixel_bytes();
+        let needed = (header.decoded_bytes() + header.strip_bytes() + region)
+            .max(self.steps(header, width, height));
+
+        if needed > self.max {
+            return Err(self.too_large(width, height, needed));
+        }
+
+        Ok(())
+    }
+
+    /// Largest step in the order of the pipeline, each is the image it works on with what it
+    /// allocates: key mattes, the detection copy with shadow masks and the cropped region, the
+    /// square canvas, the resized image with its intermediates, the edge buffers with the
+    /// cropped region and the flattened RGB image with its dithered 8 bit version
+    fn steps(&self, header: &Header, width: u32, height: u32) -> u64 {
+        let pixel_bytes = header.working_pixel_bytes();
+        let pixels = |(width, height): (u32, u32)| width as u64 * height as u64;
+        let size = (width, height);
+        let image = pixels(size) * pixel_bytes;
+
+        let key = if self.key {
+            pixels(size) * KEY_PIXEL_BYTES
+        } else {
+            0
+        };
+        let shadow = if self.shadow {
+            pixels(size) * SHADOW_PIXEL_BYTES
+        } else {
+            0
+        };
+        let copy =
+            shadow + if self.shadow_copy { image } else { 0 } + if self.crop { image } else { 0 };
+
+        let side = width.max(height);
+        let (size, square) = if self.square {
+            ((side, side), pixels((side, side)) * pixel_bytes)
+        } else {
+            (size, 0)
+        };
+        let before_resize = image + key.max(copy).max(square);
+        let image = pixels(size) * pixel_bytes;
+
+        let (size, resize) = match self.scaler {
+            Some(scaler) => {
+                let resized = scaler.output_size(size);
+                let samples = pixels((size.0, resized.1)) * RESIZE_PIXEL_BYTES;
+                let linear = if scaler.is_linear() {
+                    (pixels(size) + pixels(resized)) * RESIZE_PIXEL_BYTES
+                } else {
+                    0
+                };
+
+                (
+                    resized,
+                    image + samples + linear + pixels(resized) * pixel_bytes,
+                )
+            }
+            None => (size, 0),
+        };
+        let image = pixels(size) * pixel_bytes;
+
+        let edges = if self.edges {
+            image + pixels(size) * EDGE_PIXEL_BYTES + image
+        } else {
+            0
+        };
+        let flattened = image + image * 3 / 4 + pixels(size) * 3;
+
+        before_resize.max(resize).max(edges).max(flattened)
+    }
+
+    fn too_large(&self, width: u32, height: u32, needed: u64) -> String {
+        format!(
+            "{width}x{height} needs about {} MB, more than --max-memory {} MB",
+            needed.div_ceil(MEGABYTE),
+            self.max / MEGABYTE
+        )
+    }
+}
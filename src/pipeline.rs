@@ -0,0 +1,233 @@
+use image::{DynamicImage, RgbImage};
+
+use crate::args::Args;
+use crate::background::{self, Background};
+use crate::color;
+use crate::crop::{BorderTouch, Crop, CutPolicy, Sides};
+use crate::debug::DebugOutput;
+use crate::depth::{Depth, RgbaBuffer, SourceDepth};
+use crate::image_writer::OutputFormat;
+use crate::memory::{Strips, STRIP_ROWS};
+use crate::report::ImageReport;
+use crate::shadow::ShadowMode;
+use crate::{verbose, warning};
+
+/// Result of running the pipeline on one image
+pub enum Outcome {
+    /// Flattened image and the output name suffix listing the applied steps
+    Processed(RgbImage, String),
+    /// No step was requested, so there is nothing to save
+    Unchanged,
+    Analyzed(ImageReport),
+    /// Image was left out, e.g. by `--cut fail`
+    Skipped(String),
+    /// Image was rejected, e.g. by `--border-touch fail`
+    Failed(String),
+}
+
+/// Output file name, `steps` lists the applied steps
+pub fn output_file(image_name: &str, steps: &str, format: OutputFormat) -> String {
+    format!("{image_name}{steps}-processed.{}", format.extension())
+}
+
+/// Runs the configured steps at the bit depth of the decoded image, `source` names the input in
+/// reports and `strips` is set for images that only fit into `--max-memory` when they are
+/// cropped strip by strip
+pub fn run(
+    args: &Args,
+    source: &str,
+    image_name: &str,
+    image: DynamicImage,
+    strips: Option<Strips>,
+) -> Outcome {
+    match SourceDepth::of(&image) {
+        SourceDepth::Eight => load::<u8>(args, source, image_name, image, strips),
+        SourceDepth::Sixteen => load::<u16>(args, source, image_name, image, strips),
+        SourceDepth::Float => load::<f32>(args, source, image_name, image, strips),
+    }
+}
+
+/// Converts the image to the working format, or only its crop region when cropped in strips
+fn load<T: Depth>(
+    args: &Args,
+    source: &str,
+    image_name: &str,
+    image: DynamicImage,
+    strips: Option<Strips>,
+) -> Outcome {
+    let Some(strips) = strips else {
+        return process(args, source, image_name, T::from_dynamic(image), None);
+    };
+
+    verbose!("Cropping image {image_name} in strips of {STRIP_ROWS} rows");
+
+    let rows = |y: u32, count: u32| {
+        let strip = image.crop_imm(0, y, image.width(), count);
+        let strip = match &strips.icc_profile {
+            Some(icc_profile) => color::convert_to_srgb(&strip, icc_profile).unwrap_or(strip),
+            None => strip,
+        };
+        let mut strip = T::from_dynamic(strip);
+
+        // Alpha filtering changes the bounds, so it runs on every strip before detection
+        if let Some(alpha_filter) = args.alpha_filter {
+            background::filter_alpha(&mut strip, alpha_filter);
+        }
+
+        strip
+    };
+
+    match crop(args).crop_in_strips(
+        (image.width(), image.height()),
+        STRIP_ROWS,
+        rows,
+        |width, height| strips.budget.check_region(&strips.header, width, height),
+    ) {
+        Ok((cropped, touching)) => {
+            drop(image);
+            process(args, source, image_name, cropped, Some(touching))
+        }
+        Err(message) => Outcome::Failed(message),
+    }
+}
+
+/// Object detection and cropping settings
+fn crop(args: &Args) -> Crop {
+    let background = Background::white()
+        .with_key(args.key)
+        .with_alpha_bounds(args.alpha_bounds);
+
+    Crop::new(args.padding, background, args.border_touch)
+        .with_shadow(args.shadow)
+        .with_baseline(args.baseline)
+}
+
+/// `cropped` holds the touched sides when the image was cropped in strips already
+fn process<T: Depth>(
+    args: &Args,
+    source: &str,
+    image_name: &str,
+    mut image: RgbaBuffer<T>,
+    cropped: Option<Sides>,
+) -> Outcome {
+    let mut path = String::new();
+
+    if args.key.is_some() {
+        verbose!("Removing key colour from image {image_name}");
+        Background::white()
+            .with_key(args.key)
+            .remove_key(&mut image);
+        path.push_str("-k");
+    }
+
+    let crop = crop(args);
+
+    if args.shadow == Some(ShadowMode::Remove) {
+        verbose!("Removing shadow from image {image_name}");
+        crop.remove_shadow(&mut image);
+        path.push_str("-h");
+    }
+
+    let debug = args
+        .debug_dir
+        .as_deref()
+        .and_then(|dir| DebugOutput::new(dir, image_name, source));
+
+    if let Some(alpha_filter) = args.alpha_filter {
+        verbose!("Applying alpha filter {alpha_filter} to image {image_name}");
+        background::filter_alpha(&mut image, alpha_filter);
+        path.push_str("-a");
+    }
+
+    if args.analyze.is_some() {
+        return Outcome::Analyzed(ImageReport::analyze(
+            source,
+            &image,
+            &crop,
+            args.padding,
+            &args.edge_detection,
+        ));
+    }
+
+    if let Some((ratio_width, ratio_height)) = args.crop_to {
+        verbose!("Cropping image {image_name} to {ratio_width}:{ratio_height}");
+        let (cropped, cut) = crop.crop_to_ratio(&image, (ratio_width, ratio_height));
+
+        if cut.any() {
+            let sides = cut.names().join(", ");
+
+            if args.cut == CutPolicy::Fail {
+                return Outcome::Failed(format!("Object would be cut at: {sides}"));
+            }
+            warning!("Object in image {image_name} is cut at: {sides}");
+        }
+
+        image = cropped;
+        path.push_str("-t");
+    }
+
+    if args.crop {
+        verbose!("Cropping image {image_name}");
+        let touching = match cropped {
+            Some(touching) => touching,
+            None => match crop.crop_to_object(&image, debug.as_ref()) {
+                Ok((cropped, touching)) => {
+                    image = cropped;
+                    touching
+                }
+                Err(message) => return Outcome::Failed(message),
+            },
+        };
+
+        if touching.any() {
+            let sides = touching.names().join(", ");
+
+            if args.border_touch == Some(BorderTouch::Warn) {
+                warning!("Object in image {image_name} touches image border: {sides}");
+            } else {
+                verbose!("Object in image {image_name} touches image border: {sides}");
+            }
+        }
+        path.push_str("-c");
+    }
+
+    if !args.crop
+        && args.crop_to.is_none()
+        && !args.edge_detection.in_use
+        && !args.padding.is_empty()
+    {
+        verbose!("Padding image {image_name}");
+        image = crop.pad(image);
+        path.push_str("-p");
+    }
+
+    if args.square {
+        verbose!("Cropping image {image_name} to square");
+        image = crop.fill_to_square(image);
+        path.push_str("-s");
+    }
+
+    if let Some(scaler) = &args.scaler {
+        verbose!("Resizing image {image_name}");
+        image = scaler.resize(image);
+        path.push_str("-r");
+    }
+
+    if args.edge_detection.in_use {
+        verbose!("Detecting edges in image {image_name}");
+        image = crop.crop_to_edges_canny(&image, &args.edge_detection, debug.as_ref());
+        path.push_str("-e");
+    }
+
+    if path.is_empty() && !args.pipe {
+        return Outcome::Unchanged;
+    }
+
+    let bg = args
+        .background
+        .map_or(Background::white(), Background::from_rgb);
+
+    verbose!("Setting background {:?}", bg.color);
+
+    Outcome::Processed(T::to_rgb8(bg.set_background(&image, args.linear)), path)
+}
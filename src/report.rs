@@ -0,0 +1,196 @@
+use image::Rgba;
+use serde_json::{json, Value};
+
+use crate::args::EdgeDetectionSettings;
+use crate::background;
+use crate::crop::{Crop, ObjectInfo, Padding};
+use crate::depth::{Depth, RgbaBuffer};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+struct Detection {
+    detector: &'static str,
+    /// Tight bounds of the object pixels, fill ratio and touching sides refer to them
+    bounds: Option<ObjectInfo>,
+    /// Region the crop cuts out with padding and the image centre, can reach past the border
+    region: ObjectInfo,
+}
+
+pub struct ImageReport {
+    /// Input as given, the link for downloaded images
+    file: String,
+    width: u32,
+    height: u32,
+    padding: Padding,
+    background: Rgba<u8>,
+    detections: Vec<Detection>,
+}
+
+impl ImageReport {
+    pub fn analyze<T: Depth>(
+        file: &str,
+        image: &RgbaBuffer<T>,
+        crop: &Crop,
+        padding: Padding,
+        edge_detection: &EdgeDetectionSettings,
+    ) -> Self {
+        let bounds = crop.detect(image);
+        let mut detections = vec![Detection {
+            detector: "crop",
+            region: crop.object_region(image.dimensions(), bounds.as_ref()),
+            bounds,
+        }];
+
+        if edge_detection.in_use {
+            let bounds = crop.detect_edges(image, edge_detection);
+
+            detections.push(Detection {
+                detector: "edges",
+                region: crop.edge_region(image.dimensions(), bounds.as_ref()),
+                bounds,
+            });
+        }
+
+        Self {
+            file: file.to_string(),
+            width: image.width(),
+            height: image.height(),
+            padding,
+            background: background::estimate_background(image),
+            detections,
+        }
+    }
+
+    /// Share of the image area covered by the object bounding box
+    fn fill_ratio(&self, bounds: &ObjectInfo) -> f64 {
+        (bounds.width() as f64 * bounds.height() as f64) / (self.width as f64 * self.height as f64)
+    }
+
+    fn warnings(&self, detection: &Detection) -> Vec<&'static str> {
+        let Some(bounds) = &detection.bounds else {
+            return vec!["no_object"];
+        };
+
+        if bounds.touching_sides(self.width, self.height).any() {
+            vec!["touches_border"]
+        } else {
+            vec![]
+        }
+    }
+
+    fn touching(&self, detection: &Detection) -> Vec<&'static str> {
+        detection.bounds.as_ref().map_or(vec![], |bounds| {
+            bounds.touching_sides(self.width, self.height).names()
+        })
+    }
+
+    fn background_hex(&self) -> String {
+        let [r, g, b, a] = self.background.0;
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+
+    fn to_json(&self) -> Value {
+        let detections = self
+            .detections
+            .iter()
+            .map(|detection| {
+                json!({
+                    "detector": detection.detector,
+                    "bounds": detection.bounds.as_ref().map(json_box),
+                    "crop": json_box(&detection.region),
+                    "fill_ratio": detection
+                        .bounds
+                        .as_ref()
+                        .map(|bounds| (self.fill_ratio(bounds) * 10000.0).round() / 10000.0),
+                    "touching": self.touching(detection),
+                    "warnings": self.warnings(detection),
+                })
+            })
+            .collect::<Vec<Value>>();
+
+        json!({
+            "file": self.file,
+            "width": self.width,
+            "height": self.height,
+            "padding": self
+                .padding
+                .uniform_pixels()
+                .map_or(Value::from(self.padding.to_string()), Value::from),
+            "background": self.background_hex(),
+            "detections": detections,
+        })
+    }
+
+    fn to_csv(&self) -> Vec<String> {
+        self.detections
+            .iter()
+            .map(|detection| {
+                let bounds = detection
+                    .bounds
+                    .as_ref()
+                    .map_or(String::from(",,,"), csv_box);
+                let region = csv_box(&detection.region);
+                let fill_ratio = detection
+                    .bounds
+                    .as_ref()
+                    .map_or(String::new(), |b| format!("{:.4}", self.fill_ratio(b)));
+
+                format!(
+                    "{},{},{},{},{},{},{bounds},{region},{fill_ratio},{},{}",
+                    csv_field(&self.file),
+                    self.width,
+                    self.height,
+                    csv_field(&self.padding.to_string()),
+                    self.background_hex(),
+                    detection.detector,
+                    self.touching(detection).join(";"),
+                    self.warnings(detection).join(";"),
+                )
+            })
+            .collect()
+    }
+}
+
+pub fn print(format: ReportFormat, reports: &[ImageReport]) {
+    println!("{}", render(format, reports));
+}
+
+pub fn render(format: ReportFormat, reports: &[ImageReport]) -> String {
+    match format {
+        ReportFormat::Json => {
+            let reports = reports
+                .iter()
+                .map(|report| report.to_json().to_string())
+                .collect::<Vec<String>>()
+                .join(",\n");
+
+            format!("[\n{reports}\n]")
+        }
+        ReportFormat::Csv => std::iter::once(String::from(
+            "file,width,height,padding,background,detector,min_x,min_y,max_x,max_y,crop_min_x,crop_min_y,crop_max_x,crop_max_y,fill_ratio,touching,warnings",
+        ))
+        .chain(reports.iter().flat_map(ImageReport::to_csv))
+        .collect::<Vec<String>>()
+        .join("\n"),
+    }
+}
+
+fn json_box(b: &ObjectInfo) -> Value {
+    json!({ "min_x": b.min_x, "min_y": b.min_y, "max_x": b.max_x, "max_y": b.max_y })
+}
+
+fn csv_box(b: &ObjectInfo) -> String {
+    format!("{},{},{},{}", b.min_x, b.min_y, b.max_x, b.max_y)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -1,20 +1,86 @@
 use image::imageops::FilterType;
-use image::DynamicImage;
+use image::{DynamicImage, Pixel, Rgba, Rgba32FImage};
 
-#[derive(Debug)]
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::depth::{self, Depth, RgbaBuffer};
+use crate::verbose;
+
+#[derive(Debug, Clone, Copy)]
 pub struct Scaler {
     filter_type: FilterType,
     width: u32,
+    linear: bool,
 }
 
 impl Scaler {
-    pub fn new(filter_type: FilterType, width: u32) -> Self {
-        Self { filter_type, width }
+    pub fn new(filter_type: FilterType, width: u32, linear: bool) -> Self {
+        Self {
+            filter_type,
+            width,
+            linear,
+        }
+    }
+
+    /// Size of a resized `width`x`height` image
+    pub fn output_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        (
+            self.width,
+            (height as f32 / width as f32 * self.width as f32) as u32,
+        )
     }
 
-    pub fn resize(&self, image: DynamicImage) -> DynamicImage {
-        let height = (image.height() as f32 / image.width() as f32 * self.width as f32) as u32;
+    /// Resamples premultiplied linear light values
+    pub fn is_linear(&self) -> bool {
+        self.linear
+    }
+
+    pub fn resize<T: Depth>(&self, image: RgbaBuffer<T>) -> RgbaBuffer<T> {
+        let (_, height) = self.output_size(image.dimensions());
+
+        verbose!("Filter: {:?}", self.filter_type);
+
+        if self.linear {
+            return self.resize_linear(&image, height);
+        }
+
+        T::from_dynamic(T::into_dynamic(image).resize(self.width, height, self.filter_type))
+    }
+
+    /// Resamples premultiplied linear light values so edges don't get dark halos
+    fn resize_linear<T: Depth>(&self, image: &RgbaBuffer<T>, height: u32) -> RgbaBuffer<T> {
+        let premultiplied = Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+            let pixel = image.get_pixel(x, y).channels();
+            let alpha = pixel[3].to_unit();
+
+            Rgba([
+                srgb_to_linear(pixel[0].to_unit()) * alpha,
+                srgb_to_linear(pixel[1].to_unit()) * alpha,
+                srgb_to_linear(pixel[2].to_unit()) * alpha,
+                alpha,
+            ])
+        });
+
+        let resized = DynamicImage::ImageRgba32F(premultiplied)
+            .resize(self.width, height, self.filter_type)
+            .into_rgba32f();
+
+        RgbaBuffer::<T>::from_fn(resized.width(), resized.height(), |x, y| {
+            let pixel = resized.get_pixel(x, y).0;
+            let alpha = pixel[3].clamp(0.0, 1.0);
+            let unpremultiply = |value: f32| {
+                if alpha > 0.0 {
+                    T::from_unit(linear_to_srgb(value / alpha))
+                } else {
+                    T::zero()
+                }
+            };
 
-        image.resize(self.width, height, self.filter_type)
+            depth::rgba([
+                unpremultiply(pixel[0]),
+                unpremultiply(pixel[1]),
+                unpremultiply(pixel[2]),
+                T::from_unit(alpha),
+            ])
+        })
     }
 }
@@ -0,0 +1,545 @@
+use std::io::{Cursor, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+use image::DynamicImage;
+use serde_json::Value;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::args::{Args, LONG_FLAGS, SHORT_FLAGS, SHORT_PARAMETERS};
+use crate::exif;
+use crate::image_reader::{ImageProperties, MEGABYTE};
+use crate::image_writer::{self, OutputFormat};
+use crate::memory::Budget;
+use crate::pipeline::{self, Outcome};
+use crate::report;
+use crate::{error, status};
+
+/// Options that would make a request read or write files on the server
+const REJECTED_OPTIONS: [&str; 2] = ["debug-dir", "input-list"];
+
+/// Short and long names of the same setting, a request giving one overrides both
+const ALIASES: [(&str, &str); 5] = [
+    ("c", "crop"),
+    ("s", "square"),
+    ("v", "verbose"),
+    ("q", "quiet"),
+    ("p", "padding"),
+];
+
+/// Value of a request option
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    /// Flag set or removed, `?c`, `?c=true` or `?c=false`
+    Flag(bool),
+    Text(String),
+}
+
+/// Option name and value of a request, one letter names are short options
+pub type RequestOption = (String, OptionValue);
+
+#[derive(Debug)]
+pub struct ServeSettings {
+    pub host: String,
+    pub port: u16,
+    /// Request body limit in bytes
+    pub max_body: u64,
+    pub workers: usize,
+}
+
+impl Default for ServeSettings {
+    fn default() -> Self {
+        Self {
+            host: String::from("127.0.0.1"),
+            port: 8080,
+            max_body: 50 * MEGABYTE,
+            workers: thread::available_parallelism().map_or(4, |n| n.get()),
+        }
+    }
+}
+
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn text(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{message}\n").into_bytes(),
+        }
+    }
+}
+
+/// Serves `POST /process` and `GET /health`.
+///
+/// `defaults` are command line options applied to every request, options given
+/// in the query string or in a multipart `options` JSON field take precedence.
+pub fn run(settings: ServeSettings, defaults: Vec<String>) -> Result<(), String> {
+    let server = Arc::new(
+        Server::http((settings.host.as_str(), settings.port)).map_err(|err| {
+            format!(
+                "Failed to listen on {}:{}: {err}",
+                settings.host, settings.port
+            )
+        })?,
+    );
+    let settings = Arc::new(settings);
+    let defaults = Arc::new(defaults);
+
+    status!("Listening on http://{}:{}", settings.host, settings.port);
+
+    let workers = (0..settings.workers.max(1))
+        .map(|_| {
+            let (server, settings, defaults) = (server.clone(), settings.clone(), defaults.clone());
+
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(request, &settings, &defaults);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    Ok(())
+}
+
+fn handle(mut request: Request, settings: &ServeSettings, defaults: &[String]) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    let reply = match (&method, path) {
+        (Method::Get, "/health") => Reply::text(200, "ok"),
+        (Method::Post, "/process") => {
+            match panic::catch_unwind(AssertUnwindSafe(|| {
+                process(&mut request, query, settings, defaults)
+            })) {
+                Ok(reply) => reply,
+                Err(err) => Reply::text(500, &panic_message(err.as_ref())),
+            }
+        }
+        (_, "/health" | "/process") => Reply::text(405, "Method not allowed"),
+        _ => Reply::text(404, "Not found"),
+    };
+
+    status!("{method} {path} {}", reply.status);
+
+    let response = Response::from_data(reply.body)
+        .with_status_code(reply.status)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], reply.content_type.as_bytes())
+                .unwrap_or_else(|_| panic!("Invalid content type {}", reply.content_type)),
+        );
+
+    if let Err(err) = request.respond(response) {
+        error!("Failed to respond to {method} {path}: {err}");
+    }
+}
+
+fn process(
+    request: &mut Request,
+    query: &str,
+    settings: &ServeSettings,
+    defaults: &[String],
+) -> Reply {
+    if request
+        .body_length()
+        .is_some_and(|length| length as u64 > settings.max_body)
+    {
+        return too_large(settings);
+    }
+
+    let mut body = Vec::new();
+
+    if let Err(err) = request
+        .as_reader()
+        .take(settings.max_body + 1)
+        .read_to_end(&mut body)
+    {
+        return Reply::text(400, &format!("Failed to read request body: {err}"));
+    }
+
+    if body.len() as u64 > settings.max_body {
+        return too_large(settings);
+    }
+
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Content-Type"))
+        .map(|header| header.value.as_str().to_string())
+        .unwrap_or_default();
+
+    let mut options = match query_options(query) {
+        Ok(options) => options,
+        Err(message) => return Reply::text(400, &message),
+    };
+
+    let image = match boundary(&content_type) {
+        Some(boundary) => {
+            let parts = multipart(&body, &boundary);
+
+            if let Some(json) = parts.iter().find(|part| part.name == "options") {
+                match json_options(&json.data) {
+                    Ok(json) => options.extend(json),
+                    Err(message) => return Reply::text(400, &message),
+                }
+            }
+
+            match parts
+                .into_iter()
+                .find(|part| part.filename || part.name == "image" || part.name == "file")
+            {
+                Some(part) => part.data,
+                None => return Reply::text(400, "Multipart request has no image part"),
+            }
+        }
+        None => body,
+    };
+
+    if let Some((name, _)) = options
+        .iter()
+        .find(|(name, _)| REJECTED_OPTIONS.contains(&name.as_str()))
+    {
+        return Reply::text(
+            400,
+            &format!("Option {name} is not available in server mode"),
+        );
+    }
+
+    let argv = request_args(defaults, options);
+    // Bad options panic like on the command line, that is the client's fault
+    let args = match panic::catch_unwind(|| Args::parse(&argv)) {
+        Ok(args) => args,
+        Err(err) => return Reply::text(400, &panic_message(err.as_ref())),
+    };
+
+    let image_properties: ImageProperties<DynamicImage> = match ImageProperties::read_from(
+        Cursor::new(image),
+        "upload",
+        args.icc_mode,
+        Budget::new(&args).as_ref(),
+    ) {
+        Ok(props) => props,
+        Err(message) => return Reply::text(400, &message),
+    };
+
+    let mut metadata = image_properties.metadata;
+    metadata.exif = exif::output_exif(metadata.exif, args.metadata_policy);
+
+    match pipeline::run(
+        &args,
+        "upload",
+        "upload",
+        image_properties.image,
+        image_properties.strips,
+    ) {
+        Outcome::Processed(rgb_image, _) => {
+            let mut encoded = Vec::new();
+
+            match image_writer::write(&rgb_image, &mut encoded, args.format, &metadata, "response")
+            {
+                Ok(()) => Reply {
+                    status: 200,
+                    content_type: mime_type(args.format),
+                    body: encoded,
+                },
+                Err(message) => Reply::text(500, &message),
+            }
+        }
+        Outcome::Analyzed(image_report) => {
+            let format = args.analyze.unwrap_or(report::ReportFormat::Json);
+
+            Reply {
+                status: 200,
+                content_type: match format {
+                    report::ReportFormat::Json => "application/json",
+                    report::ReportFormat::Csv => "text/csv",
+                },
+                body: report::render(format, &[image_report]).into_bytes(),
+            }
+        }
+        Outcome::Skipped(message) | Outcome::Failed(message) => Reply::text(422, &message),
+        Outcome::Unchanged => Reply::text(422, "Nothing to do"),
+    }
+}
+
+fn too_large(settings: &ServeSettings) -> Reply {
+    Reply::text(
+        413,
+        &format!(
+            "Request body is larger than {} MB",
+            settings.max_body / MEGABYTE
+        ),
+    )
+}
+
+fn mime_type(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Jpeg => "image/jpeg",
+        OutputFormat::Png => "image/png",
+        OutputFormat::Webp => "image/webp",
+    }
+}
+
+fn panic_message(err: &(dyn std::any::Any + Send)) -> String {
+    err.downcast_ref::<String>()
+        .cloned()
+        .or_else(|| {
+            err.downcast_ref::<&str>()
+                .map(|message| message.to_string())
+        })
+        .unwrap_or_else(|| String::from("Processing panicked"))
+}
+
+/// Checks a request option name, an empty value or `true` sets a flag and `false` removes it
+pub fn option(name: &str, value: Option<String>) -> Result<RequestOption, String> {
+    if let [letter] = name.chars().collect::<Vec<char>>()[..] {
+        if !SHORT_FLAGS.contains(letter) && !SHORT_PARAMETERS.contains(letter) {
+            return Err(format!("Unknown option {name}"));
+        }
+    }
+
+    let value = match value.as_deref() {
+        None | Some("") | Some("true") => OptionValue::Flag(true),
+        Some("false") => OptionValue::Flag(false),
+        Some(_) => OptionValue::Text(value.unwrap_or_default()),
+    };
+
+    Ok((name.to_string(), value))
+}
+
+/// Command line for a request: the serve defaults without the options the request gives,
+/// then the request options, the last one of a name wins. `-` makes the pipeline always output.
+pub fn request_args(defaults: &[String], options: Vec<RequestOption>) -> Vec<String> {
+    let mut merged: Vec<RequestOption> = Vec::new();
+
+    for (name, value) in options {
+        merged.retain(|(merged_name, _)| *merged_name != name);
+        merged.push((name, value));
+    }
+
+    let names = merged
+        .iter()
+        .flat_map(|(name, _)| {
+            ALIASES
+                .iter()
+                .filter(move |(short, long)| short == name || long == name)
+                .flat_map(|(short, long)| [*short, *long])
+                .chain(std::iter::once(name.as_str()))
+        })
+        .collect::<Vec<&str>>();
+
+    let requested = merged.iter().flat_map(|(name, value)| {
+        let flag = if name.chars().count() == 1 {
+            format!("-{name}")
+        } else {
+            format!("--{name}")
+        };
+
+        match value {
+            OptionValue::Flag(true) => vec![flag],
+            OptionValue::Flag(false) => vec![],
+            OptionValue::Text(text) => vec![flag, text.clone()],
+        }
+    });
+
+    std::iter::once(String::from("imoyo"))
+        .chain(without_options(defaults, &names))
+        .chain(requested)
+        .chain(std::iter::once(String::from("-")))
+        .collect()
+}
+
+/// Command line arguments without the named options and their values
+fn without_options(args: &[String], names: &[&str]) -> Vec<String> {
+    let mut rest = Vec::new();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = &args[i];
+
+        if let Some(name) = arg.strip_prefix("--") {
+            let value = args.get(i + 1).filter(|_| !LONG_FLAGS.contains(&name));
+
+            if !names.contains(&name) {
+                rest.push(arg.clone());
+                rest.extend(value.cloned());
+            }
+            i += 1 + value.is_some() as usize;
+        } else if let Some(letters) = arg.strip_prefix('-').filter(|letters| !letters.is_empty()) {
+            let takes_value = |letters: &str| letters.chars().any(|c| SHORT_PARAMETERS.contains(c));
+            let value = args.get(i + 1).filter(|_| takes_value(letters));
+            let kept = letters
+                .chars()
+                .filter(|letter| !names.contains(&letter.to_string().as_str()))
+                .collect::<String>();
+
+            if !kept.is_empty() {
+                rest.push(format!("-{kept}"));
+            }
+            if takes_value(&kept) {
+                rest.extend(value.cloned());
+            }
+            i += 1 + value.is_some() as usize;
+        } else {
+            rest.push(arg.clone());
+            i += 1;
+        }
+    }
+
+    rest
+}
+
+/// `?c&s&w=500&format=png` as request options
+pub fn query_options(query: &str) -> Result<Vec<RequestOption>, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once('=')
+                .map_or((pair, None), |(name, value)| (name, Some(value)));
+
+            option(&percent_decode(name), value.map(percent_decode))
+        })
+        .collect()
+}
+
+/// `{"c": true, "w": 500, "format": "png"}` as request options
+pub fn json_options(data: &[u8]) -> Result<Vec<RequestOption>, String> {
+    let json: Value =
+        serde_json::from_slice(data).map_err(|err| format!("Invalid options JSON: {err}"))?;
+    let object = json
+        .as_object()
+        .ok_or_else(|| String::from("Options JSON must be an object"))?;
+
+    object
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::Bool(flag) => Some(flag.to_string()),
+                Value::String(value) => Some(value.clone()),
+                Value::Null => None,
+                value => Some(value.to_string()),
+            };
+
+            option(name, value)
+        })
+        .collect()
+}
+
+/// Decodes `%xx` escapes and `+` of a query string
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub struct Part {
+    pub name: String,
+    /// The part is a file upload
+    pub filename: bool,
+    pub data: Vec<u8>,
+}
+
+/// Boundary of a `multipart/form-data` content type
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';').map(str::trim);
+
+    if !params
+        .next()
+        .is_some_and(|mime| mime.eq_ignore_ascii_case("multipart/form-data"))
+    {
+        return None;
+    }
+
+    params
+        .find_map(|param| param.strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"').to_string())
+}
+
+/// Splits a `multipart/form-data` body into its parts
+pub fn multipart(body: &[u8], boundary: &str) -> Vec<Part> {
+    let delimiter = format!("--{boundary}").into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = match find(body, &delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return parts,
+    };
+
+    while let Some(end) = find(rest, &delimiter) {
+        let part = rest[..end].strip_prefix(b"\r\n").unwrap_or(&rest[..end]);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+
+        if let Some(split) = find(part, b"\r\n\r\n") {
+            let headers = String::from_utf8_lossy(&part[..split]);
+            let disposition = headers
+                .lines()
+                .find(|line| {
+                    line.to_ascii_lowercase()
+                        .starts_with("content-disposition:")
+                })
+                .unwrap_or("");
+
+            parts.push(Part {
+                name: disposition_value(disposition, "name").unwrap_or_default(),
+                filename: disposition_value(disposition, "filename").is_some(),
+                data: part[split + 4..].to_vec(),
+            });
+        }
+
+        rest = &rest[end + delimiter.len()..];
+
+        if rest.starts_with(b"--") {
+            break;
+        }
+    }
+
+    parts
+}
+
+fn disposition_value(disposition: &str, key: &str) -> Option<String> {
+    disposition
+        .split(';')
+        .map(str::trim)
+        .find_map(|param| param.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim_matches('"').to_string())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use image::Pixel;
+
+use crate::background::{self, Background};
+use crate::color::to_ycbcr;
+use crate::depth::{Depth, RgbaBuffer};
+
+/// Shadows darken the background without tinting it, so their chroma stays this close to it
+const MAX_CHROMA: f32 = 0.04;
+
+/// Shadows take at most this much luma from the background, darker pixels belong to the object
+const MAX_DARKENING: f32 = 0.5;
+
+/// Shadows fade out softly, a larger luma step between neighbours is the edge of the object
+const MAX_STEP: f32 = 0.05;
+
+/// How cast shadows next to the object are treated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowMode {
+    /// Shadows are part of the crop bounds, as without the option
+    Include,
+    /// Shadows don't extend the crop bounds, but are kept inside them
+    Exclude,
+    /// Shadows are made transparent, so they take the background colour
+    Remove,
+}
+
+/// Marks the soft shadows that fade into the background.
+///
+/// Shadows are grown from pixels next to the background that are only slightly darker than it,
+/// through pixels close to the background hue, and stop at the first hard luma step. Pixels are
+/// judged as they look on white, so semi-transparent shadows of cutouts are found too.
+pub fn detect<T: Depth>(image: &RgbaBuffer<T>, bg: &Background) -> Vec<bool> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let [bg_luma, bg_cb, bg_cr] = on_white(background::estimate_background(image).channels());
+
+    let looks = image
+        .pixels()
+        .map(|pixel| on_white(pixel.channels()))
+        .collect::<Vec<[f32; 3]>>();
+    let is_background = image
+        .pixels()
+        .map(|pixel| bg.is_background::<T>(*pixel))
+        .collect::<Vec<bool>>();
+
+    let candidates = looks
+        .iter()
+        .zip(&is_background)
+        .map(|([luma, cb, cr], is_background)| {
+            !is_background
+                && *luma >= bg_luma - MAX_DARKENING
+                && (cb - bg_cb).hypot(cr - bg_cr) <= MAX_CHROMA
+        })
+        .collect::<Vec<bool>>();
+
+    let smooth = |i: usize, j: usize| (looks[i][0] - looks[j][0]).abs() <= MAX_STEP;
+
+    let mut shadow = vec![false; width * height];
+    let mut queue = (0..width * height)
+        .filter(|&i| {
+            candidates[i] && neighbours(i, width, height).any(|j| is_background[j] && smooth(i, j))
+        })
+        .collect::<VecDeque<usize>>();
+
+    for &i in &queue {
+        shadow[i] = true;
+    }
+
+    while let Some(i) = queue.pop_front() {
+        for j in neighbours(i, width, height) {
+            if candidates[j] && !shadow[j] && smooth(i, j) {
+                shadow[j] = true;
+                queue.push_back(j);
+            }
+        }
+    }
+
+    shadow
+}
+
+/// Makes the marked pixels fully transparent
+pub fn clear<T: Depth>(image: &mut RgbaBuffer<T>, shadow: &[bool]) {
+    for (pixel, _) in image
+        .chunks_exact_mut(4)
+        .zip(shadow)
+        .filter(|(_, shadow)| **shadow)
+    {
+        pixel.fill(T::zero());
+    }
+}
+
+/// Luma and chroma of the pixel composited over white
+fn on_white<T: Depth>(channels: &[T]) -> [f32; 3] {
+    let alpha = channels[3].to_unit();
+
+    to_ycbcr([0, 1, 2].map(|c| channels[c].to_unit() * alpha + 1.0 - alpha))
+}
+
+/// Indices of the four pixels next to pixel `i`
+fn neighbours(i: usize, width: usize, height: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (i % width, i / width);
+
+    [
+        (x > 0).then(|| i - 1),
+        (x + 1 < width).then(|| i + 1),
+        (y > 0).then(|| i - width),
+        (y + 1 < height).then(|| i + width),
+    ]
+    .into_iter()
+    .flatten()
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use image::DynamicImage;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::args::Args;
+use crate::exif;
+use crate::image_reader::ImageProperties;
+use crate::image_writer;
+use crate::input;
+use crate::memory::Budget;
+use crate::pipeline::{self, Outcome};
+use crate::{error, status};
+
+#[derive(Debug)]
+pub struct WatchSettings {
+    pub dir: PathBuf,
+    pub out_dir: PathBuf,
+    /// How long a file size must stay unchanged before the file counts as written
+    pub settle: Duration,
+    /// Log file, appended to across runs
+    pub log: PathBuf,
+}
+
+/// Size of a file that is still being written and when it last changed
+struct Pending {
+    size: u64,
+    changed: Instant,
+}
+
+/// Processes images dropped into `dir` until the watcher stops.
+///
+/// Originals are moved to `done/` or `failed/` inside `dir`, images already
+/// there on start are processed first.
+pub fn run(settings: WatchSettings, args: &Args) -> Result<(), String> {
+    if args.analyze.is_some() {
+        return Err(String::from("--analyze is not available in watch mode"));
+    }
+
+    let dir = fs::canonicalize(&settings.dir)
+        .map_err(|err| format!("Failed to open {:?}: {err}", settings.dir))?;
+
+    // Outputs saved into the watched folder would be picked up and processed again
+    if resolve(&settings.out_dir).starts_with(&dir) {
+        return Err(String::from("--out-dir must be outside the watched folder"));
+    }
+
+    let done = settings.dir.join("done");
+    let failed = settings.dir.join("failed");
+
+    for dir in [&settings.out_dir, &done, &failed] {
+        fs::create_dir_all(dir).map_err(|err| format!("Failed to create {dir:?}: {err}"))?;
+    }
+
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&settings.log)
+        .map_err(|err| format!("Failed to open log {:?}: {err}", settings.log))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|err| format!("Failed to start watcher: {err}"))?;
+    watcher
+        .watch(&settings.dir, RecursiveMode::NonRecursive)
+        .map_err(|err| format!("Failed to watch {:?}: {err}", settings.dir))?;
+
+    status!("Watching {:?}", settings.dir);
+
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+
+    for entry in fs::read_dir(&settings.dir)
+        .map_err(|err| format!("Failed to read dir {:?}: {err}", settings.dir))?
+        .flatten()
+    {
+        add(&mut pending, entry.path());
+    }
+
+    loop {
+        match receiver.recv_timeout(settings.settle / 2) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    event
+                        .paths
+                        .into_iter()
+                        .for_each(|path| add(&mut pending, path));
+                }
+            }
+            Ok(Err(err)) => error!("Watch error: {err}"),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        for path in written(&mut pending, settings.settle) {
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(|| process(&path, &settings.out_dir, args)))
+                    .unwrap_or_else(|_| Err(String::from("Processing panicked")));
+
+            let (target, message) = match result {
+                Ok(message) => (&done, message),
+                Err(message) => (&failed, message),
+            };
+            let state = if target == &done { "done" } else { "failed" };
+
+            let message = match move_into(&path, target) {
+                Ok(()) => message,
+                Err(err) => format!("{message}, {err}"),
+            };
+
+            if target == &done {
+                status!("{state} {path:?}: {message}");
+            } else {
+                error!("{state} {path:?}: {message}");
+            }
+            write_log(&mut log, state, &path, &message);
+        }
+    }
+}
+
+/// Absolute path with symlinks resolved, also for a path that doesn't exist yet
+fn resolve(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    path.ancestors()
+        .find_map(|ancestor| {
+            let canonical = fs::canonicalize(ancestor).ok()?;
+            Some(canonical.join(path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.clone())
+}
+
+/// Tracks image files directly inside the watched dir
+fn add(pending: &mut HashMap<PathBuf, Pending>, path: PathBuf) {
+    if path.is_file() && path.to_str().is_some_and(input::is_image) {
+        pending.entry(path).or_insert(Pending {
+            size: u64::MAX,
+            changed: Instant::now(),
+        });
+    }
+}
+
+/// Removes and returns the files whose size didn't change for `settle`
+fn written(pending: &mut HashMap<PathBuf, Pending>, settle: Duration) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+
+    pending.retain(|path, file| {
+        let Ok(metadata) = fs::metadata(path) else {
+            // Removed or moved away before it was written completely
+            return false;
+        };
+
+        if metadata.len() != file.size {
+            file.size = metadata.len();
+            file.changed = Instant::now();
+            true
+        } else if file.changed.elapsed() >= settle {
+            ready.push(path.clone());
+            false
+        } else {
+            true
+        }
+    });
+
+    ready.sort();
+    ready
+}
+
+/// Runs the pipeline and saves the result into `out_dir`
+fn process(path: &Path, out_dir: &Path, args: &Args) -> Result<String, String> {
+    let image_path = path
+        .to_str()
+        .ok_or_else(|| format!("Invalid unicode in {path:?}"))?;
+    let image_properties: ImageProperties<DynamicImage> =
+        ImageProperties::read(image_path, args.icc_mode, Budget::new(args).as_ref())?;
+
+    let image_name = image_properties.name;
+    let mut metadata = image_properties.metadata;
+    metadata.exif = exif::output_exif(metadata.exif, args.metadata_policy);
+
+    match pipeline::run(
+        args,
+        image_path,
+        &image_name,
+        image_properties.image,
+        image_properties.strips,
+    ) {
+        Outcome::Processed(rgb_image, steps) => {
+            let output = out_dir.join(pipeline::output_file(&image_name, &steps, args.format));
+            let output_path = output
+                .to_str()
+                .ok_or_else(|| format!("Invalid unicode in {output:?}"))?;
+
+            image_writer::save(&rgb_image, output_path, args.format, &metadata)?;
+
+            Ok(format!("saved {output_path} {:?}", rgb_image.dimensions()))
+        }
+        Outcome::Unchanged => Ok(String::from("nothing to do")),
+        Outcome::Skipped(message) | Outcome::Failed(message) => Err(message),
+        Outcome::Analyzed(_) => Err(String::from("--analyze is not available in watch mode")),
+    }
+}
+
+/// Moves the original, a timestamp is added when the name is taken already
+fn move_into(path: &Path, dir: &Path) -> Result<(), String> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid file name {path:?}"))?;
+    let mut target = dir.join(name);
+
+    if target.exists() {
+        target = dir.join(format!("{}-{}", timestamp(), name.to_string_lossy()));
+    }
+
+    fs::rename(path, &target).map_err(|err| format!("failed to move to {target:?}: {err}"))
+}
+
+fn write_log(log: &mut File, state: &str, path: &Path, message: &str) {
+    if let Err(err) = writeln!(log, "{} {state} {} {message}", timestamp(), path.display()) {
+        error!("Failed to write log: {err}");
+    }
+}
+
+/// Seconds since the Unix epoch
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
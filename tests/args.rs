@@ -0,0 +1,119 @@
+use imoyo::args::Args;
+use imoyo::crop::{Length, Padding};
+
+fn args(options: &[&str]) -> Args {
+    Args::parse(
+        &std::iter::once("imoyo")
+            .chain(options.iter().copied())
+            .map(String::from)
+            .collect::<Vec<String>>(),
+    )
+}
+
+#[test]
+fn long_aliases_match_short_flags() {
+    let short = args(&["-cs", "in.jpg"]);
+    let long = args(&["--crop", "--square", "in.jpg"]);
+
+    assert!(long.crop && long.square);
+    assert_eq!(short.settings_key(), long.settings_key());
+}
+
+#[test]
+fn long_option_values_are_not_options() {
+    let args = args(&["--padding", "10", "--format", "png", "in.jpg"]);
+
+    assert!(!args.crop);
+}
+
+#[test]
+#[should_panic(expected = "Unknown option --cs")]
+fn unknown_long_option_is_rejected() {
+    args(&["--cs", "in.jpg"]);
+}
+
+#[test]
+#[should_panic(expected = "Key tolerance must be positive")]
+fn zero_key_tolerance_is_rejected() {
+    args(&["--key", "0,177,64", "--key-tolerance", "0", "in.jpg"]);
+}
+
+#[test]
+#[should_panic(expected = "--key-tolerance only applies to --key")]
+fn key_tolerance_needs_a_key() {
+    args(&["--key-tolerance", "20", "in.jpg"]);
+}
+
+#[test]
+#[should_panic(expected = "Settle time must be at least")]
+fn zero_settle_time_is_rejected() {
+    Args::watch_settings(
+        &[
+            "watch",
+            "incoming",
+            "--out-dir",
+            "processed",
+            "--settle",
+            "0",
+        ]
+        .map(String::from),
+    );
+}
+
+#[test]
+fn automatic_edge_thresholds_take_a_blur_sigma() {
+    let settings = args(&["-e", "auto,2", "in.jpg"]).edge_detection;
+
+    assert!(settings.in_use && settings.auto);
+    assert_eq!(settings.blur_sigma, Some(2.0));
+}
+
+#[test]
+#[should_panic(expected = "Blur sigma must be positive")]
+fn zero_blur_sigma_is_rejected() {
+    args(&["-e", "1,10,0", "in.jpg"]);
+}
+
+#[test]
+fn padding_takes_one_to_four_sides() {
+    let padding = |value: &str| args(&["--padding", value, "in.jpg"]).padding;
+
+    assert_eq!(padding("10"), Padding::uniform(Length::Pixels(10)));
+    assert_eq!(
+        padding("5%,10px"),
+        Padding {
+            top: Length::Percent(5.0),
+            right: Length::Pixels(10),
+            bottom: Length::Percent(5.0),
+            left: Length::Pixels(10),
+        }
+    );
+    assert_eq!(
+        padding("1,2,3"),
+        Padding {
+            top: Length::Pixels(1),
+            right: Length::Pixels(2),
+            bottom: Length::Pixels(3),
+            left: Length::Pixels(2),
+        }
+    );
+    assert_eq!(padding("0,0,15%,0").to_string(), "0,0,15%,0");
+}
+
+#[test]
+#[should_panic(expected = "Failed to parse length: -5%")]
+fn negative_percent_padding_is_rejected() {
+    args(&["--padding", "-5%", "in.jpg"]);
+}
+
+#[test]
+#[should_panic(expected = "Padding must be top,right,bottom,left")]
+fn more_than_four_padding_sides_are_rejected() {
+    args(&["--padding", "1,2,3,4,5", "in.jpg"]);
+}
+
+#[test]
+#[should_panic(expected = "--baseline only applies to the square canvas of -s")]
+fn baseline_needs_a_square_canvas() {
+    args(&["--baseline", "10%", "in.jpg"]);
+}
@@ -0,0 +1,91 @@
+use image::{DynamicImage, Rgb, Rgba, RgbaImage};
+use imoyo::args::Args;
+use imoyo::background::{Background, ChromaKey};
+use imoyo::pipeline::{self, Outcome};
+
+#[test]
+fn blending_rounds_to_the_nearest_value() {
+    // 255 * 127 / 255 + 1 * 128 / 255 = 127.5, truncating gives 127
+    let image = RgbaImage::from_pixel(1, 1, Rgba([1, 1, 1, 128]));
+
+    let blended = Background::white().set_background(&image, false);
+
+    assert_eq!(blended.get_pixel(0, 0), &Rgb([128, 128, 128]));
+}
+
+const GREEN: Rgb<u8> = Rgb([0, 177, 64]);
+
+/// Red block at 20..40 x 10..30 on a green screen with a shadow below it at 20..40 x 30..35
+fn green_screen() -> RgbaImage {
+    RgbaImage::from_fn(60, 40, |x, y| match (x, y) {
+        (20..40, 10..30) => Rgba([200, 30, 30, 255]),
+        (20..40, 30..35) => Rgba([0, 90, 32, 255]),
+        _ => Rgba([0, 177, 64, 255]),
+    })
+}
+
+fn keyed(image: &mut RgbaImage) -> Background {
+    let background = Background::white().with_key(Some(ChromaKey::new(GREEN, 0.3).unwrap()));
+    background.remove_key(image);
+
+    background
+}
+
+#[test]
+fn key_colour_and_its_shadows_become_transparent() {
+    let mut image = green_screen();
+    keyed(&mut image);
+
+    assert_eq!(image.get_pixel(0, 0).0[3], 0);
+    assert_eq!(image.get_pixel(30, 32).0[3], 0);
+    // Inside the object, out of reach of the spill suppression
+    assert_eq!(image.get_pixel(30, 20), &Rgba([200, 30, 30, 255]));
+}
+
+#[test]
+fn key_colour_cast_is_removed_from_object_edges() {
+    let mut image = green_screen();
+    // Object edge with a green cast
+    image.put_pixel(20, 20, Rgba([100, 150, 90, 255]));
+    keyed(&mut image);
+
+    let edge = image.get_pixel(20, 20);
+    assert_eq!(edge.0[3], 255);
+    assert!(edge.0[1] < 150, "{edge:?}");
+}
+
+#[test]
+fn keyed_pixels_are_background() {
+    let mut image = green_screen();
+    let background = keyed(&mut image);
+
+    assert!(background.is_background::<u8>(*image.get_pixel(0, 0)));
+    assert!(!background.is_background::<u8>(*image.get_pixel(30, 20)));
+}
+
+#[test]
+fn keyed_image_is_cropped_to_the_object() {
+    let args = Args::parse(&["imoyo", "-c", "--key", "0,177,64", "in.png"].map(String::from));
+
+    let outcome = pipeline::run(
+        &args,
+        "in.png",
+        "in",
+        DynamicImage::ImageRgba8(green_screen()),
+        None,
+    );
+
+    match outcome {
+        Outcome::Processed(output, _) => {
+            assert_eq!(output.dimensions(), (20, 20));
+            // Keyed pixels are flattened onto white, not back onto green
+            assert_eq!(output.get_pixel(10, 10), &Rgb([200, 30, 30]));
+        }
+        _ => panic!("Image was not processed"),
+    }
+}
+
+#[test]
+fn grey_key_is_rejected() {
+    assert!(ChromaKey::new(Rgb([128, 128, 128]), 0.3).is_err());
+}
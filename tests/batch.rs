@@ -0,0 +1,31 @@
+use std::process::Command;
+
+use imoyo::batch::{Batch, ImageResult, Status};
+
+#[test]
+fn batch_fails_when_any_input_failed() {
+    let mut batch = Batch::new(false);
+    batch.record(ImageResult::new("a.png"));
+    batch.record(ImageResult {
+        status: Status::Skipped(String::from("Output is up to date")),
+        ..ImageResult::new("b.png")
+    });
+    assert!(!batch.any_failed());
+
+    batch.record(ImageResult::failed(
+        "c.png",
+        String::from("Can't find image"),
+    ));
+    assert!(batch.any_failed());
+}
+
+#[test]
+fn missing_input_exits_with_an_error() {
+    let status = Command::new(env!("CARGO_BIN_EXE_imoyo"))
+        .args(["-q", "-c", "nothere.png"])
+        .current_dir(std::env::temp_dir())
+        .status()
+        .unwrap();
+
+    assert_eq!(status.code(), Some(1));
+}
@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use image::{Rgb, RgbImage};
+use imoyo::cache::MANIFEST;
+
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("imoyo-{}-cache-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+fn imoyo(dir: &Path, args: &[&str]) {
+    Command::new(env!("CARGO_BIN_EXE_imoyo"))
+        .arg("-q")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+}
+
+#[test]
+fn runs_that_save_nothing_leave_no_manifest() {
+    let dir = work_dir("nothing");
+
+    imoyo(&dir, &["-c", "nothere.png"]);
+    imoyo(&dir, &["--analyze", "json", "nothere.png"]);
+
+    assert!(!dir.join(MANIFEST).exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn first_saved_output_creates_the_manifest() {
+    let dir = work_dir("saved");
+    RgbImage::from_pixel(20, 20, Rgb([200, 30, 30]))
+        .save(dir.join("in.png"))
+        .unwrap();
+
+    imoyo(&dir, &["-s", "in.png"]);
+
+    let manifest = fs::read_to_string(dir.join(MANIFEST)).unwrap();
+    assert_eq!(manifest.lines().count(), 1);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
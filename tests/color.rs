@@ -0,0 +1,61 @@
+use std::fs::File;
+
+use image::codecs::png::PngEncoder;
+use image::{DynamicImage, ImageEncoder, Rgba, RgbaImage};
+use imoyo::color::{self, IccMode};
+use imoyo::image_reader::ImageProperties;
+use moxcms::ColorProfile;
+
+fn image() -> RgbaImage {
+    RgbaImage::from_pixel(4, 4, Rgba([200, 100, 50, 255]))
+}
+
+/// Reads a PNG with the given embedded profile
+fn read_with_profile(name: &str, icc_profile: Vec<u8>) -> Result<DynamicImage, String> {
+    let path = std::env::temp_dir().join(format!("imoyo-{}-{name}.png", std::process::id()));
+    let mut encoder = PngEncoder::new(File::create(&path).unwrap());
+
+    encoder.set_icc_profile(icc_profile).unwrap();
+    encoder
+        .write_image(&image(), 4, 4, image::ExtendedColorType::Rgba8)
+        .unwrap();
+
+    let read =
+        ImageProperties::<DynamicImage>::read(path.to_str().unwrap(), IccMode::Convert, None)
+            .map(|properties| properties.image);
+    std::fs::remove_file(&path).unwrap();
+
+    read
+}
+
+#[test]
+fn rgb_profile_is_converted() {
+    let profile = ColorProfile::new_display_p3().encode().unwrap();
+    let converted = color::convert_to_srgb(&DynamicImage::ImageRgba8(image()), &profile).unwrap();
+
+    assert_ne!(
+        converted.to_rgba8().get_pixel(0, 0),
+        &Rgba([200, 100, 50, 255])
+    );
+}
+
+#[test]
+fn lab_profile_is_not_converted() {
+    let profile = ColorProfile::new_lab().encode().unwrap();
+
+    assert!(color::convert_to_srgb(&DynamicImage::ImageRgba8(image()), &profile).is_err());
+}
+
+#[test]
+fn unsupported_profile_keeps_pixels() {
+    let read = read_with_profile("lab", ColorProfile::new_lab().encode().unwrap()).unwrap();
+
+    assert_eq!(read.to_rgba8(), image());
+}
+
+#[test]
+fn invalid_profile_keeps_pixels() {
+    let read = read_with_profile("invalid", vec![0; 200]).unwrap();
+
+    assert_eq!(read.to_rgba8(), image());
+}
@@ -0,0 +1,426 @@
+use image::{DynamicImage, Rgb, Rgba, RgbaImage};
+use imoyo::args::{Args, EdgeDetectionSettings};
+use imoyo::background::Background;
+use imoyo::crop::{self, BorderTouch, Crop, Length, Padding, Sides};
+use imoyo::pipeline::{self, Outcome};
+
+/// Dark rectangle on white that almost reaches the left and right border
+fn wide_object() -> RgbaImage {
+    RgbaImage::from_fn(60, 40, |x, y| {
+        if (2..58).contains(&x) && (5..35).contains(&y) {
+            Rgba([20, 20, 20, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    })
+}
+
+/// Dark block on a white image, `object` is `(min_x, min_y, max_x, max_y)` inclusive
+fn block((width, height): (u32, u32), object: Option<(u32, u32, u32, u32)>) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| match object {
+        Some((min_x, min_y, max_x, max_y))
+            if (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y) =>
+        {
+            Rgba([20, 20, 20, 255])
+        }
+        _ => Rgba([255, 255, 255, 255]),
+    })
+}
+
+fn padded(pixels: u32) -> Crop {
+    Crop::new(
+        Padding::uniform(Length::Pixels(pixels)),
+        Background::white(),
+        None,
+    )
+}
+
+#[test]
+fn edge_crop_pads_past_image_border() {
+    let crop = Crop::new(
+        Padding::uniform(Length::Pixels(10)),
+        Background::white(),
+        None,
+    );
+    let settings = EdgeDetectionSettings {
+        in_use: true,
+        auto: false,
+        low_threshold: 1.0,
+        high_threshold: 10.0,
+        blur_sigma: None,
+    };
+
+    let cropped = crop.crop_to_edges_canny(&wide_object(), &settings, None);
+
+    assert!(cropped.width() > 60);
+    assert_eq!(cropped.get_pixel(0, 0), &Background::white().pixel::<u8>());
+    assert_eq!(
+        cropped.get_pixel(cropped.width() / 2, cropped.height() / 2),
+        &Rgba([20, 20, 20, 255])
+    );
+}
+
+#[test]
+fn object_region_is_the_cropped_area() {
+    let image = wide_object();
+
+    for border_touch in [None, Some(BorderTouch::NoPad)] {
+        let crop = Crop::new(
+            Padding::uniform(Length::Pixels(10)),
+            Background::white(),
+            border_touch,
+        );
+
+        let region = crop.object_region(image.dimensions(), crop.detect(&image).as_ref());
+        let (cropped, _) = crop.crop_to_object(&image, None).unwrap();
+
+        assert_eq!((region.width(), region.height()), cropped.dimensions());
+    }
+}
+
+#[test]
+fn crop_to_wide_ratio() {
+    let image = block((200, 100), Some((90, 40, 109, 59)));
+
+    let (cropped, cut) = padded(0).crop_to_ratio(&image, (2, 1));
+
+    assert_eq!(cropped.dimensions(), (40, 20));
+    assert!(!cut.any());
+    assert_eq!(cropped.get_pixel(20, 10), &Rgba([20, 20, 20, 255]));
+}
+
+#[test]
+fn crop_to_tall_ratio() {
+    let image = block((200, 100), Some((90, 40, 109, 59)));
+
+    let (cropped, cut) = padded(0).crop_to_ratio(&image, (1, 2));
+
+    assert_eq!(cropped.dimensions(), (20, 40));
+    assert!(!cut.any());
+    assert_eq!(cropped.get_pixel(10, 20), &Rgba([20, 20, 20, 255]));
+}
+
+#[test]
+fn crop_to_ratio_window_holds_padding() {
+    let image = block((200, 100), Some((90, 40, 109, 59)));
+
+    let (cropped, cut) = padded(10).crop_to_ratio(&image, (1, 1));
+
+    assert_eq!(cropped.dimensions(), (40, 40));
+    assert!(!cut.any());
+    assert_eq!(cropped.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+    assert_eq!(cropped.get_pixel(10, 10), &Rgba([20, 20, 20, 255]));
+}
+
+#[test]
+fn crop_to_ratio_padding_is_limited_by_image() {
+    let image = block((200, 100), Some((90, 40, 109, 59)));
+
+    let (cropped, cut) = padded(60).crop_to_ratio(&image, (1, 1));
+
+    // The padded object doesn't fit, the largest square of the image keeps the object whole
+    assert_eq!(cropped.dimensions(), (100, 100));
+    assert!(!cut.any());
+}
+
+#[test]
+fn crop_to_ratio_reports_cut_sides() {
+    let image = block((100, 100), Some((0, 40, 99, 59)));
+
+    let (cropped, cut) = padded(0).crop_to_ratio(&image, (1, 2));
+
+    assert_eq!(cropped.dimensions(), (50, 100));
+    assert_eq!(cut.names(), vec!["right"]);
+}
+
+#[test]
+fn crop_to_ratio_without_object_cuts_nothing() {
+    let image = block((200, 100), None);
+
+    let (cropped, cut) = padded(0).crop_to_ratio(&image, (1, 1));
+
+    assert_eq!(cropped.dimensions(), (100, 100));
+    assert!(!cut.any());
+}
+
+#[test]
+fn window_longer_than_range_is_centred_on_weights() {
+    let weights = [0, 0, 5, 5, 0, 0, 0, 0];
+
+    assert_eq!(crop::place_window(&weights, (2, 3), 4), 1);
+    // Kept inside the axis at the border
+    assert_eq!(crop::place_window(&[5, 0, 0, 0], (0, 0), 3), 0);
+}
+
+#[test]
+fn window_shorter_than_range_covers_most_weight() {
+    let weights = [1, 3, 3, 1, 0];
+
+    assert_eq!(crop::place_window(&weights, (0, 3), 2), 1);
+}
+
+/// Crops an object touching the left border with 5 pixels of padding
+fn touching_left(border_touch: Option<BorderTouch>) -> Result<(RgbaImage, Sides), String> {
+    let image = block((60, 40), Some((0, 10, 29, 29)));
+
+    Crop::new(
+        Padding::uniform(Length::Pixels(5)),
+        Background::white(),
+        border_touch,
+    )
+    .crop_to_object(&image, None)
+}
+
+#[test]
+fn border_touch_warn_keeps_the_padding() {
+    let (cropped, touching) = touching_left(Some(BorderTouch::Warn)).unwrap();
+
+    // Object stretched to the image centre at x = 30
+    assert_eq!(cropped.dimensions(), (41, 30));
+    assert_eq!(touching.names(), vec!["left"]);
+    assert_eq!(cropped.get_pixel(0, 15), &Background::white().pixel::<u8>());
+    assert_eq!(cropped.get_pixel(5, 15), &Rgba([20, 20, 20, 255]));
+}
+
+#[test]
+fn border_touch_fail_rejects_the_image() {
+    assert_eq!(
+        touching_left(Some(BorderTouch::Fail)).err(),
+        Some(String::from("Object touches image border: left"))
+    );
+
+    let (_, touching) = Crop::new(
+        Padding::default(),
+        Background::white(),
+        Some(BorderTouch::Fail),
+    )
+    .crop_to_object(&wide_object(), None)
+    .unwrap();
+    assert!(!touching.any());
+}
+
+#[test]
+fn border_touch_no_pad_leaves_touching_sides_unpadded() {
+    let (cropped, touching) = touching_left(Some(BorderTouch::NoPad)).unwrap();
+
+    assert_eq!(cropped.dimensions(), (36, 30));
+    assert_eq!(touching.names(), vec!["left"]);
+    assert_eq!(cropped.get_pixel(0, 15), &Rgba([20, 20, 20, 255]));
+}
+
+#[test]
+fn border_touch_fail_is_a_failed_outcome() {
+    let image = block((60, 40), Some((0, 10, 29, 29)));
+    let run = |policy: &str| {
+        let args =
+            Args::parse(&["imoyo", "-c", "--border-touch", policy, "in.png"].map(String::from));
+
+        pipeline::run(
+            &args,
+            "in.png",
+            "in",
+            DynamicImage::ImageRgba8(image.clone()),
+            None,
+        )
+    };
+
+    assert!(matches!(
+        run("fail"),
+        Outcome::Failed(message) if message == "Object touches image border: left"
+    ));
+    assert!(matches!(run("warn"), Outcome::Processed(..)));
+}
+
+/// Opaque white block at 20..40 x 10..30 with a soft black shadow below it at 20..40 x 30..36,
+/// on a transparent image
+fn cut_out() -> RgbaImage {
+    RgbaImage::from_fn(60, 40, |x, y| match (x, y) {
+        (20..40, 10..30) => Rgba([255, 255, 255, 255]),
+        (20..40, 30..36) => Rgba([0, 0, 0, 60]),
+        _ => Rgba([0, 0, 0, 0]),
+    })
+}
+
+fn alpha_bounds(threshold: u8) -> Crop {
+    Crop::new(
+        Padding::default(),
+        Background::white().with_alpha_bounds(Some(threshold)),
+        None,
+    )
+}
+
+#[test]
+fn alpha_bounds_find_objects_of_any_colour() {
+    let (cropped, _) = alpha_bounds(128).crop_to_object(&cut_out(), None).unwrap();
+
+    // White object, the shadow is less opaque than the threshold
+    assert_eq!(cropped.dimensions(), (20, 20));
+}
+
+#[test]
+fn alpha_bounds_include_pixels_at_the_threshold() {
+    let (cropped, _) = alpha_bounds(60).crop_to_object(&cut_out(), None).unwrap();
+
+    assert_eq!(cropped.dimensions(), (20, 26));
+    // Soft shadow keeps its transparency
+    assert_eq!(cropped.get_pixel(10, 22), &Rgba([0, 0, 0, 60]));
+}
+
+#[test]
+fn alpha_bounds_crop_is_blended_with_the_background() {
+    let args = Args::parse(&["imoyo", "-c", "--alpha-bounds", "50", "in.png"].map(String::from));
+
+    match pipeline::run(
+        &args,
+        "in.png",
+        "in",
+        DynamicImage::ImageRgba8(cut_out()),
+        None,
+    ) {
+        Outcome::Processed(output, _) => {
+            assert_eq!(output.dimensions(), (20, 26));
+            assert_eq!(output.get_pixel(10, 5), &Rgb([255, 255, 255]));
+            assert_eq!(output.get_pixel(10, 22), &Rgb([195, 195, 195]));
+        }
+        _ => panic!("Image was not processed"),
+    }
+}
+
+#[test]
+fn padding_is_added_per_side() {
+    let image = block((200, 100), Some((90, 40, 109, 59)));
+    let crop = Crop::new(
+        Padding {
+            top: Length::Pixels(5),
+            right: Length::Percent(50.0),
+            bottom: Length::Percent(25.0),
+            left: Length::Pixels(0),
+        },
+        Background::white(),
+        None,
+    );
+
+    let (cropped, _) = crop.crop_to_object(&image, None).unwrap();
+
+    // 50% of the object width on the right, 25% of its height below
+    assert_eq!(cropped.dimensions(), (30, 30));
+    assert_eq!(cropped.get_pixel(0, 5), &Rgba([20, 20, 20, 255]));
+    assert_eq!(cropped.get_pixel(19, 24), &Rgba([20, 20, 20, 255]));
+    // Padding inside the image keeps the image pixels
+    assert_eq!(cropped.get_pixel(20, 24), &Rgba([255, 255, 255, 255]));
+    assert_eq!(cropped.get_pixel(19, 25), &Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+fn percent_padding_without_crop_is_relative_to_the_image() {
+    let image = block((200, 100), None);
+    let crop = Crop::new(
+        Padding::uniform(Length::Percent(10.0)),
+        Background::white(),
+        None,
+    );
+
+    assert_eq!(crop.pad(image).dimensions(), (240, 120));
+}
+
+#[test]
+fn percent_padding_is_rounded() {
+    let image = block((200, 100), Some((90, 40, 109, 59)));
+    let crop = Crop::new(
+        Padding {
+            bottom: Length::Percent(12.5),
+            ..Padding::default()
+        },
+        Background::white(),
+        None,
+    );
+
+    let (cropped, _) = crop.crop_to_object(&image, None).unwrap();
+
+    // 2.5 pixels below the 20 pixel object
+    assert_eq!(cropped.dimensions(), (20, 23));
+}
+
+fn on_baseline(margin: Length) -> Crop {
+    Crop::new(Padding::default(), Background::white(), None).with_baseline(Some(margin))
+}
+
+fn is_dark(pixel: &Rgba<u8>) -> bool {
+    pixel.0 == [20, 20, 20, 255]
+}
+
+#[test]
+fn baseline_sets_the_space_below_the_object() {
+    let image = block((100, 60), Some((40, 10, 59, 29)));
+
+    let square = on_baseline(Length::Pixels(5)).fill_to_square(image);
+
+    assert_eq!(square.dimensions(), (100, 100));
+    // Object bottom 5 pixels above the lower edge, centred horizontally
+    assert!(is_dark(square.get_pixel(40, 94)) && is_dark(square.get_pixel(59, 75)));
+    assert!(!is_dark(square.get_pixel(50, 95)));
+    assert!(!is_dark(square.get_pixel(39, 94)) && !is_dark(square.get_pixel(60, 94)));
+}
+
+#[test]
+fn baseline_centres_the_centre_of_mass() {
+    // Column at x 20..30 on a foot reaching to x 60, its mass is centred at x 30
+    let image = RgbaImage::from_fn(100, 60, |x, y| {
+        if (20..30).contains(&x) && y < 45 || (20..60).contains(&x) && (40..45).contains(&y) {
+            Rgba([20, 20, 20, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+
+    let square = on_baseline(Length::Pixels(0)).fill_to_square(image);
+
+    assert_eq!(square.dimensions(), (100, 100));
+    assert!(is_dark(square.get_pixel(40, 99)) && is_dark(square.get_pixel(49, 60)));
+    assert!(!is_dark(square.get_pixel(39, 99)) && !is_dark(square.get_pixel(50, 60)));
+}
+
+#[test]
+fn canvas_grows_to_hold_the_baseline() {
+    let image = block((100, 60), Some((40, 0, 59, 59)));
+
+    let square = on_baseline(Length::Percent(50.0)).fill_to_square(image);
+
+    // 60 pixel object above half of the canvas
+    assert_eq!(square.dimensions(), (120, 120));
+    assert!(is_dark(square.get_pixel(60, 0)) && is_dark(square.get_pixel(60, 59)));
+    assert_eq!(square.get_pixel(60, 60), &Background::white().pixel::<u8>());
+}
+
+#[test]
+fn canvas_grows_to_centre_the_centre_of_mass() {
+    // Most of the mass in a block at the left border, a thin arm reaches the right border
+    let image = RgbaImage::from_fn(100, 100, |x, y| {
+        if x < 20 && y >= 60 || (60..70).contains(&y) {
+            Rgba([20, 20, 20, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+
+    let square = on_baseline(Length::Pixels(0)).fill_to_square(image);
+
+    // Centre of mass at x 35, the arm ends 65 pixels right of it
+    assert_eq!(square.dimensions(), (130, 130));
+    assert!(is_dark(square.get_pixel(129, 90)) && is_dark(square.get_pixel(30, 129)));
+    assert_eq!(
+        square.get_pixel(29, 129),
+        &Background::white().pixel::<u8>()
+    );
+}
+
+#[test]
+fn baseline_without_object_centres_the_image() {
+    let image = block((100, 60), None);
+
+    let square = on_baseline(Length::Pixels(5)).fill_to_square(image);
+
+    assert_eq!(square.dimensions(), (100, 100));
+    assert_eq!(square.get_pixel(50, 0), &Background::white().pixel::<u8>());
+    assert_eq!(square.get_pixel(50, 50), &Rgba([255, 255, 255, 255]));
+}
@@ -0,0 +1,109 @@
+use std::fs;
+use std::path::PathBuf;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use imoyo::args::Args;
+use imoyo::pipeline::{self, Outcome};
+use serde_json::Value;
+
+fn debug_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("imoyo-{}-debug-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    dir
+}
+
+/// Runs the pipeline on a dark block on white and lists the files written to `dir`
+fn debug_files(dir: &PathBuf, options: &[&str], source: &str) -> Vec<String> {
+    let image = RgbImage::from_fn(60, 40, |x, y| {
+        if (20..40).contains(&x) && (10..30).contains(&y) {
+            Rgb([20, 20, 20])
+        } else {
+            Rgb([255, 255, 255])
+        }
+    });
+    let args = Args::parse(
+        &["imoyo", "--debug-dir", dir.to_str().unwrap()]
+            .iter()
+            .chain(options)
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>(),
+    );
+
+    let outcome = pipeline::run(&args, source, "img", DynamicImage::ImageRgb8(image), None);
+    assert!(matches!(
+        outcome,
+        Outcome::Processed(..) | Outcome::Unchanged
+    ));
+
+    let mut files = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    files.sort();
+
+    files
+}
+
+/// File names without the source hash, `img-1a2b3c4d-mask.png` as `mask.png`
+fn stages(files: &[String]) -> Vec<&str> {
+    files.iter().map(|file| &file[13..]).collect()
+}
+
+#[test]
+fn crop_writes_mask_overlay_and_region() {
+    let dir = debug_dir("crop");
+
+    let files = debug_files(&dir, &["-c", "-p", "5"], "a/img.png");
+    assert_eq!(
+        stages(&files),
+        vec!["crop-object.json", "crop-overlay.png", "mask.png"]
+    );
+
+    let json: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join(&files[0])).unwrap()).unwrap();
+    assert_eq!(json["stage"], "crop");
+    assert_eq!(
+        (&json["min_x"], &json["max_x"], &json["width"]),
+        (&Value::from(15), &Value::from(44), &Value::from(30))
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn edges_write_edge_map_overlay_and_region() {
+    let dir = debug_dir("edges");
+
+    let files = debug_files(&dir, &["-e", "1,10"], "a/img.png");
+    assert_eq!(
+        stages(&files),
+        vec!["edges-object.json", "edges-overlay.png", "edges.png"]
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn other_steps_write_nothing() {
+    let dir = debug_dir("none");
+
+    assert!(debug_files(&dir, &["-s"], "a/img.png").is_empty());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn equal_names_from_different_sources_are_kept_apart() {
+    let dir = debug_dir("sources");
+
+    debug_files(&dir, &["-c"], "a/img.png");
+    let files = debug_files(&dir, &["-c"], "b/img.png");
+
+    assert_eq!(files.len(), 6);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
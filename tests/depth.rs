@@ -0,0 +1,71 @@
+use image::{DynamicImage, ImageBuffer, RgbImage, Rgba, RgbaImage};
+use imoyo::args::Args;
+use imoyo::depth::{Depth, SourceDepth};
+use imoyo::pipeline::{self, Outcome};
+
+/// Flattened output of squaring `image`
+fn squared(image: DynamicImage) -> RgbImage {
+    let args = Args::parse(&["imoyo", "-s", "in.png"].map(String::from));
+
+    match pipeline::run(&args, "in.png", "in", image, None) {
+        Outcome::Processed(output, _) => output,
+        _ => panic!("Image was not processed"),
+    }
+}
+
+fn mean(output: &RgbImage) -> f64 {
+    output.pixels().map(|p| p.0[0] as f64).sum::<f64>() / (output.width() * output.height()) as f64
+}
+
+#[test]
+fn sixteen_bit_source_is_dithered_to_its_level() {
+    // A quarter of the way from 8 bit level 100 to 101
+    let level = (100.25 / 255.0 * 65535.0_f32).round() as u16;
+    let image = ImageBuffer::from_pixel(64, 64, Rgba([level, level, level, 65535]));
+
+    let output = squared(DynamicImage::ImageRgba16(image));
+
+    assert!(output.pixels().all(|p| [100, 101].contains(&p.0[0])));
+    assert!((mean(&output) - 100.25).abs() < 0.01, "{}", mean(&output));
+}
+
+#[test]
+fn float_source_is_dithered_to_its_level() {
+    let image = ImageBuffer::from_pixel(64, 64, Rgba([0.5f32, 0.5, 0.5, 1.0]));
+
+    let output = squared(DynamicImage::ImageRgba32F(image));
+
+    assert!((mean(&output) - 127.5).abs() < 0.01, "{}", mean(&output));
+}
+
+#[test]
+fn eight_bit_source_is_kept_exact() {
+    let image = RgbaImage::from_pixel(64, 64, Rgba([100, 100, 100, 255]));
+
+    let output = squared(DynamicImage::ImageRgba8(image));
+
+    assert!(output.pixels().all(|p| p.0 == [100, 100, 100]));
+}
+
+#[test]
+fn integer_channels_are_rounded_and_clamped() {
+    assert_eq!(u8::from_f32(99.6), 100);
+    assert_eq!(u16::from_f32(99.4), 99);
+    assert_eq!(u8::from_unit(0.5), 128);
+    assert_eq!(u16::from_unit(2.0), 65535);
+    assert_eq!(f32::from_unit(-1.0), 0.0);
+}
+
+#[test]
+fn working_depth_follows_the_source() {
+    let depth = |image: DynamicImage| match SourceDepth::of(&image) {
+        SourceDepth::Eight => 8,
+        SourceDepth::Sixteen => 16,
+        SourceDepth::Float => 32,
+    };
+
+    assert_eq!(depth(DynamicImage::new_luma8(1, 1)), 8);
+    assert_eq!(depth(DynamicImage::new_rgb16(1, 1)), 16);
+    assert_eq!(depth(DynamicImage::new_luma_a16(1, 1)), 16);
+    assert_eq!(depth(DynamicImage::new_rgba32f(1, 1)), 32);
+}
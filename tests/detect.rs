@@ -0,0 +1,95 @@
+use image::{GenericImageView, Rgba, RgbaImage};
+use imoyo::background::Background;
+use imoyo::crop::{Crop, Length, ObjectInfo, Padding};
+
+/// Small linear congruential generator, so the random images are the same on every run
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, max: u32) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % max as u64) as u32
+    }
+}
+
+/// White image with random blocks, lines and single pixels of object and near white colours
+fn random_image(random: &mut Random) -> RgbaImage {
+    let (width, height) = (1 + random.below(120), 1 + random.below(120));
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+
+    for _ in 0..random.below(6) {
+        let (x, y) = (random.below(width), random.below(height));
+        let (w, h) = match random.below(4) {
+            0 => (1, 1),
+            1 => (1 + random.below(4), 1),
+            2 => (1, 1 + random.below(4)),
+            _ => (1 + random.below(40), 1 + random.below(40)),
+        };
+        let color = match random.below(4) {
+            0 => Rgba([253, 254, 253, 255]),
+            1 => Rgba([0, 0, 0, 0]),
+            2 => Rgba([40, 80, 120, 128]),
+            _ => Rgba([90, 60, 30, 255]),
+        };
+
+        for y in y..(y + h).min(height) {
+            for x in x..(x + w).min(width) {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    image
+}
+
+fn bounds(object: Option<ObjectInfo>) -> Option<(i32, i32, i32, i32)> {
+    object.map(|object| (object.min_x, object.min_y, object.max_x, object.max_y))
+}
+
+#[test]
+fn proxy_detection_equals_full_scan() {
+    let crop = Crop::new(Padding::default(), Background::white(), None);
+    let mut random = Random(7);
+
+    for _ in 0..2000 {
+        let image = random_image(&mut random);
+        assert_eq!(
+            bounds(crop.detect(&image)),
+            bounds(crop.detect_full_scan(&image)),
+            "{}x{} image",
+            image.width(),
+            image.height()
+        );
+    }
+}
+
+#[test]
+fn strip_crop_equals_full_crop() {
+    let crop = Crop::new(
+        Padding::uniform(Length::Pixels(3)),
+        Background::white(),
+        None,
+    );
+    let mut random = Random(11);
+
+    for _ in 0..500 {
+        let image = random_image(&mut random);
+        let strip_rows = 1 + random.below(9);
+
+        let (full, full_touching) = crop.crop_to_object(&image, None).unwrap();
+        let (strips, strips_touching) = crop
+            .crop_in_strips(
+                image.dimensions(),
+                strip_rows,
+                |y, count| image.view(0, y, image.width(), count).to_image(),
+                |_, _| Ok(()),
+            )
+            .unwrap();
+
+        assert_eq!(full, strips, "{strip_rows} rows per strip");
+        assert_eq!(full_touching.names(), strips_touching.names());
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::{ImageFormat, Rgb, RgbImage};
+use imoyo::download::{DownloadSettings, Downloads};
+use tiny_http::{Header, Response, Server, StatusCode};
+
+/// Status, content type and body of one scripted response
+type Reply = (u16, &'static str, Vec<u8>);
+
+/// Local server that answers requests with `replies` in order and counts them
+fn serve(replies: Vec<Reply>) -> (String, Arc<AtomicUsize>) {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://127.0.0.1:{}/images/product.png",
+        server.server_addr().to_ip().unwrap().port()
+    );
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+
+    thread::spawn(move || {
+        for (status, content_type, body) in replies {
+            let Ok(request) = server.recv() else {
+                return;
+            };
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            let length = body.len();
+            let response = Response::new(
+                StatusCode(status),
+                vec![Header::from_bytes("Content-Type", content_type).unwrap()],
+                Cursor::new(body),
+                // Large bodies are sent chunked, so only the streamed size check can catch them
+                (length < 1000).then_some(length),
+                None,
+            );
+            let _ = request.respond(response);
+        }
+    });
+
+    (url, requests)
+}
+
+fn png() -> Vec<u8> {
+    let mut png = Vec::new();
+    RgbImage::from_pixel(2, 2, Rgb([90, 60, 30]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+
+    png
+}
+
+fn downloads(retries: u32) -> Downloads {
+    Downloads::new(DownloadSettings {
+        timeout: Duration::from_secs(5),
+        retries,
+        max_size: 10_000,
+    })
+}
+
+#[test]
+fn retries_server_errors() {
+    let (url, requests) = serve(vec![
+        (500, "text/plain", Vec::new()),
+        (503, "text/plain", Vec::new()),
+        (200, "image/png", png()),
+    ]);
+
+    let path = downloads(2).fetch(&url).unwrap();
+
+    assert!(path.ends_with("product.png"));
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn gives_up_after_the_last_retry() {
+    let (url, requests) = serve(vec![(502, "text/plain", Vec::new()); 3]);
+
+    let message = downloads(1).fetch(&url).unwrap_err();
+
+    assert!(message.contains("HTTP status 502"), "{message}");
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn rejects_other_content_types() {
+    let (url, requests) = serve(vec![(200, "text/html", b"<html></html>".to_vec()); 3]);
+
+    let message = downloads(2).fetch(&url).unwrap_err();
+
+    assert!(
+        message.contains("Unexpected content type text/html"),
+        "{message}"
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn aborts_downloads_over_the_size_limit() {
+    let mut body = png();
+    body.resize(50_000, 0);
+    let (url, requests) = serve(vec![(200, "image/png", body); 3]);
+
+    let message = downloads(2).fetch(&url).unwrap_err();
+
+    assert!(message.contains("Image is larger than"), "{message}");
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn rejects_bodies_that_are_not_images() {
+    let (url, _) = serve(vec![(200, "image/png", b"definitely not a png".to_vec())]);
+
+    let message = downloads(0).fetch(&url).unwrap_err();
+
+    assert!(message.contains("not a known image format"), "{message}");
+}
+
+#[test]
+fn fails_malformed_urls_without_retrying() {
+    let start = Instant::now();
+
+    let message = downloads(2).fetch("http://[::1/image.png").unwrap_err();
+
+    assert!(message.starts_with("Failed to download image"), "{message}");
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[test]
+fn removes_downloads_when_dropped() {
+    let (url, _) = serve(vec![(200, "image/png", png())]);
+    let mut downloads = downloads(0);
+
+    let path = downloads.fetch(&url).unwrap();
+    assert!(Path::new(&path).exists());
+
+    drop(downloads);
+    assert!(!Path::new(&path).exists());
+    assert!(!Path::new(&path).parent().unwrap().exists());
+}
+
+#[test]
+fn removes_a_download_once_it_is_done() {
+    let (url, _) = serve(vec![(200, "image/png", png()), (200, "image/png", png())]);
+    let mut downloads = downloads(0);
+
+    let first = downloads.fetch(&url).unwrap();
+    downloads.remove(&first);
+    assert!(!Path::new(&first).parent().unwrap().exists());
+
+    let second = downloads.fetch(&url).unwrap();
+    assert!(Path::new(&second).exists());
+}
@@ -0,0 +1,111 @@
+use image::{GrayImage, Luma};
+use imoyo::args::EdgeDetectionSettings;
+use imoyo::edges;
+
+fn auto(blur_sigma: Option<f32>) -> EdgeDetectionSettings {
+    EdgeDetectionSettings {
+        in_use: true,
+        auto: true,
+        low_threshold: 0.0,
+        high_threshold: 0.0,
+        blur_sigma,
+    }
+}
+
+fn fixed(
+    low_threshold: f32,
+    high_threshold: f32,
+    blur_sigma: Option<f32>,
+) -> EdgeDetectionSettings {
+    EdgeDetectionSettings {
+        in_use: true,
+        auto: false,
+        low_threshold,
+        high_threshold,
+        blur_sigma,
+    }
+}
+
+/// Block at 20..60 x 20..60 on an 80x80 image, `noise` is added to every other pixel
+fn block(background: u8, object: u8, noise: u8) -> GrayImage {
+    GrayImage::from_fn(80, 80, |x, y| {
+        let value = if (20..60).contains(&x) && (20..60).contains(&y) {
+            object
+        } else {
+            background
+        };
+
+        Luma([if (x + y) % 2 == 0 {
+            value.saturating_add(noise)
+        } else {
+            value
+        }])
+    })
+}
+
+fn edge_pixels(edges: &GrayImage) -> Vec<(u32, u32)> {
+    edges
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[0] > 0)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+#[test]
+fn auto_thresholds_find_the_object_outline() {
+    let (edges, low_threshold, high_threshold) = edges::canny(&block(240, 40, 0), &auto(None));
+
+    assert_eq!(low_threshold, high_threshold / 2.0);
+
+    let pixels = edge_pixels(&edges);
+    assert!(!pixels.is_empty());
+    // Only the outline of the block, nothing in its inside or far outside
+    assert!(pixels
+        .iter()
+        .all(|&(x, y)| (17..63).contains(&x) && (17..63).contains(&y)));
+    assert!(pixels
+        .iter()
+        .all(|&(x, y)| !((24..56).contains(&x) && (24..56).contains(&y))));
+}
+
+#[test]
+fn auto_thresholds_follow_the_contrast() {
+    let (_, _, strong) = edges::canny(&block(240, 40, 0), &auto(None));
+    let (edges, _, weak) = edges::canny(&block(130, 110, 0), &auto(None));
+
+    assert!(weak < strong / 4.0, "{weak} against {strong}");
+    // Weak contrast is still found
+    assert!(!edge_pixels(&edges).is_empty());
+}
+
+#[test]
+fn flat_image_uses_default_thresholds() {
+    let (edges, low_threshold, high_threshold) = edges::canny(&block(200, 200, 0), &auto(None));
+
+    assert_eq!((low_threshold, high_threshold), (1.0, 10.0));
+    assert!(edge_pixels(&edges).is_empty());
+}
+
+#[test]
+fn fixed_thresholds_are_used_as_given() {
+    let (_, low_threshold, high_threshold) =
+        edges::canny(&block(240, 40, 0), &fixed(12.0, 34.0, None));
+
+    assert_eq!((low_threshold, high_threshold), (12.0, 34.0));
+}
+
+#[test]
+fn blur_removes_noise_edges() {
+    let image = block(200, 40, 60);
+
+    let noisy = edge_pixels(&edges::canny(&image, &fixed(20.0, 40.0, None)).0);
+    let blurred = edge_pixels(&edges::canny(&image, &fixed(20.0, 40.0, Some(3.0))).0);
+
+    assert!(
+        blurred.len() < noisy.len(),
+        "{} against {}",
+        blurred.len(),
+        noisy.len()
+    );
+    assert!(!blurred.is_empty());
+}
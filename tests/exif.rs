@@ -0,0 +1,206 @@
+use imoyo::exif::{self, MetadataPolicy};
+
+const IMAGE_DESCRIPTION: u16 = 0x010E;
+const MAKE: u16 = 0x010F;
+const ARTIST: u16 = 0x013B;
+const COPYRIGHT: u16 = 0x8298;
+
+const ASCII: u16 = 2;
+
+/// TIFF chunk with IFD0 at offset 8 holding `fields`, longer values follow the IFD
+fn tiff(little_endian: bool, fields: &[(u16, &[u8])]) -> Vec<u8> {
+    let u16_bytes = |value: u16| {
+        if little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    };
+    let u32_bytes = |value: u32| {
+        if little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    };
+
+    let mut chunk = if little_endian {
+        vec![0x49, 0x49, 42, 0]
+    } else {
+        vec![0x4d, 0x4d, 0, 42]
+    };
+    chunk.extend_from_slice(&u32_bytes(8));
+    chunk.extend_from_slice(&u16_bytes(fields.len() as u16));
+
+    let mut data = Vec::new();
+    let data_start = 8 + 2 + fields.len() * 12 + 4;
+
+    for (tag, value) in fields {
+        chunk.extend_from_slice(&u16_bytes(*tag));
+        chunk.extend_from_slice(&u16_bytes(ASCII));
+        chunk.extend_from_slice(&u32_bytes(value.len() as u32));
+
+        if value.len() <= 4 {
+            let mut inline = [0u8; 4];
+            inline[..value.len()].copy_from_slice(value);
+            chunk.extend_from_slice(&inline);
+        } else {
+            chunk.extend_from_slice(&u32_bytes((data_start + data.len()) as u32));
+            data.extend_from_slice(value);
+        }
+    }
+
+    chunk.extend_from_slice(&[0; 4]);
+    chunk.extend_from_slice(&data);
+
+    chunk
+}
+
+/// Tags and values of a little endian chunk as written by `copyright_fields`
+fn read_fields(chunk: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    assert_eq!(&chunk[..8], &[0x49, 0x49, 42, 0, 8, 0, 0, 0]);
+
+    let u16_at = |offset: usize| u16::from_le_bytes([chunk[offset], chunk[offset + 1]]);
+    let u32_at = |offset: usize| u32::from_le_bytes(chunk[offset..offset + 4].try_into().unwrap());
+
+    (0..u16_at(8) as usize)
+        .map(|i| {
+            let entry = 10 + i * 12;
+            assert_eq!(u16_at(entry + 2), ASCII);
+
+            let count = u32_at(entry + 4) as usize;
+            let start = if count <= 4 {
+                entry + 8
+            } else {
+                let offset = u32_at(entry + 8) as usize;
+                assert_eq!(offset % 2, 0, "values start on a word boundary");
+                offset
+            };
+
+            (u16_at(entry), chunk[start..start + count].to_vec())
+        })
+        .collect()
+}
+
+fn fields() -> Vec<(u16, &'static [u8])> {
+    vec![
+        (COPYRIGHT, b"(c) Example Shop 2024\0"),
+        (MAKE, b"Camera maker\0"),
+        (ARTIST, b"Jo\0"),
+        (IMAGE_DESCRIPTION, b"Red shoe, left\0"),
+    ]
+}
+
+fn expected() -> Vec<(u16, Vec<u8>)> {
+    vec![
+        (IMAGE_DESCRIPTION, b"Red shoe, left\0".to_vec()),
+        (ARTIST, b"Jo\0".to_vec()),
+        (COPYRIGHT, b"(c) Example Shop 2024\0".to_vec()),
+    ]
+}
+
+#[test]
+fn keeps_copyright_fields_of_little_endian_exif() {
+    let chunk = exif::copyright_fields(&tiff(true, &fields()))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(read_fields(&chunk), expected());
+}
+
+#[test]
+fn keeps_copyright_fields_of_big_endian_exif() {
+    let chunk = exif::copyright_fields(&tiff(false, &fields()))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(read_fields(&chunk), expected());
+}
+
+#[test]
+fn output_reads_back_unchanged() {
+    let chunk = exif::copyright_fields(&tiff(false, &fields()))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(exif::copyright_fields(&chunk), Ok(Some(chunk.clone())));
+}
+
+#[test]
+fn short_values_are_inline() {
+    let chunk = exif::copyright_fields(&tiff(true, &[(ARTIST, b"Abc\0")]))
+        .unwrap()
+        .unwrap();
+
+    // Header, one entry and the next IFD offset, no value data after the IFD
+    assert_eq!(chunk.len(), 8 + 2 + 12 + 4);
+    assert_eq!(read_fields(&chunk), vec![(ARTIST, b"Abc\0".to_vec())]);
+}
+
+#[test]
+fn long_values_are_stored_at_an_offset() {
+    let chunk = exif::copyright_fields(&tiff(true, &[(ARTIST, b"Abcd\0")]))
+        .unwrap()
+        .unwrap();
+
+    // Odd length value is padded to a word
+    assert_eq!(chunk.len(), 8 + 2 + 12 + 4 + 6);
+    assert_eq!(read_fields(&chunk), vec![(ARTIST, b"Abcd\0".to_vec())]);
+}
+
+#[test]
+fn exif_without_copyright_fields_is_dropped() {
+    assert_eq!(
+        exif::copyright_fields(&tiff(true, &[(MAKE, b"Camera maker\0")])),
+        Ok(None)
+    );
+}
+
+#[test]
+fn missing_ifd_is_an_error() {
+    let mut chunk = tiff(true, &fields());
+    chunk[4..8].copy_from_slice(&1000u32.to_le_bytes());
+
+    assert!(exif::copyright_fields(&chunk).is_err());
+    assert!(exif::copyright_fields(&chunk[..8]).is_err());
+}
+
+#[test]
+fn corrupt_value_offset_is_an_error() {
+    let mut chunk = tiff(true, &[(COPYRIGHT, b"(c) Example Shop\0")]);
+    // Value offset of the first entry
+    chunk[18..22].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert!(exif::copyright_fields(&chunk).is_err());
+}
+
+#[test]
+fn invalid_header_is_an_error() {
+    assert!(exif::copyright_fields(b"II*").is_err());
+    assert!(exif::copyright_fields(b"XX\0*\0\0\0\x08").is_err());
+}
+
+#[test]
+fn truncated_exif_never_panics() {
+    for little_endian in [true, false] {
+        let chunk = tiff(little_endian, &fields());
+
+        for end in 0..chunk.len() {
+            assert!(
+                exif::copyright_fields(&chunk[..end]).is_err(),
+                "{end} bytes"
+            );
+        }
+    }
+}
+
+#[test]
+fn corrupt_exif_is_dropped_from_the_output() {
+    let chunk = tiff(true, &fields());
+
+    assert_eq!(
+        exif::output_exif(Some(chunk[..20].to_vec()), MetadataPolicy::KeepCopyright),
+        None
+    );
+    assert!(exif::output_exif(Some(chunk), MetadataPolicy::KeepCopyright).is_some());
+}
@@ -0,0 +1,108 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use image::{Rgb, RgbImage};
+use imoyo::input::{self, Input};
+
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("imoyo-{}-input-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// Sources and output names read from a list file holding `content`
+fn read(name: &str, content: &str) -> Vec<(String, Option<String>)> {
+    let dir = work_dir(name);
+    let list = dir.join("list.txt");
+    fs::write(&list, content).unwrap();
+
+    let inputs = input::read_list(list.to_str().unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    inputs
+        .into_iter()
+        .map(|Input { source, name }| (source, name))
+        .collect()
+}
+
+#[test]
+fn plain_list_has_one_source_per_line() {
+    assert_eq!(
+        read(
+            "plain",
+            "# Shoes\na.jpg\n\n  https://example.com/b.png  \nimages/\n"
+        ),
+        vec![
+            (String::from("a.jpg"), None),
+            (String::from("https://example.com/b.png"), None),
+            (String::from("images/"), None),
+        ]
+    );
+}
+
+#[test]
+fn csv_list_names_the_outputs() {
+    assert_eq!(
+        read(
+            "csv",
+            "SKU,Title,URL\n\
+             A-1,\"Shoe, red\",https://example.com/a.jpg\n\
+             b/2,\"Say \"\"hi\"\"\",b.jpg\n\
+             ,No name,c.jpg\n\
+             D-4,No source,\n"
+        ),
+        vec![
+            (
+                String::from("https://example.com/a.jpg"),
+                Some(String::from("A-1"))
+            ),
+            (String::from("b.jpg"), Some(String::from("b_2"))),
+            (String::from("c.jpg"), None),
+        ]
+    );
+}
+
+#[test]
+fn csv_list_without_name_column_keeps_file_names() {
+    assert_eq!(
+        read("path", "path\na.jpg\nb.jpg\n"),
+        vec![(String::from("a.jpg"), None), (String::from("b.jpg"), None)]
+    );
+}
+
+#[test]
+fn missing_list_is_an_error() {
+    assert!(input::read_list("nothere.txt")
+        .unwrap_err()
+        .starts_with("Failed to read input list nothere.txt"));
+}
+
+#[test]
+fn list_from_stdin_is_processed_with_output_names() {
+    let dir = work_dir("stdin");
+    RgbImage::from_pixel(20, 20, Rgb([200, 30, 30]))
+        .save(dir.join("in.png"))
+        .unwrap();
+
+    let mut imoyo = Command::new(env!("CARGO_BIN_EXE_imoyo"))
+        .args(["-q", "-s", "--input-list", "-"])
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    imoyo
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"name,path\nshoe-1,in.png\n")
+        .unwrap();
+
+    assert!(imoyo.wait().unwrap().success());
+    assert!(dir.join("shoe-1-s-processed.jpg").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
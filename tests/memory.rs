@@ -0,0 +1,113 @@
+use std::io::Cursor;
+
+use image::codecs::png::PngDecoder;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use imoyo::args::Args;
+use imoyo::color::IccMode;
+use imoyo::image_reader::ImageProperties;
+use imoyo::memory::{Budget, Header, Plan};
+use imoyo::pipeline::{self, Outcome};
+
+/// Tall 100x2800 image, its RGBA copy alone doesn't fit into 1 MB next to the decoded pixels
+fn write_tall_image(name: &str) -> String {
+    let image = RgbImage::from_fn(100, 2800, |x, y| {
+        if (30..70).contains(&x) && (1380..1420).contains(&y) {
+            Rgb([90, 60, 30])
+        } else {
+            Rgb([255, 255, 255])
+        }
+    });
+    let path = std::env::temp_dir().join(format!("imoyo-{}-{name}.png", std::process::id()));
+    image.save(&path).unwrap();
+
+    path.to_str().unwrap().to_string()
+}
+
+fn args(options: &[&str]) -> Args {
+    Args::parse(
+        &std::iter::once("imoyo")
+            .chain(options.iter().copied())
+            .map(String::from)
+            .collect::<Vec<String>>(),
+    )
+}
+
+fn process(args: &Args, path: &str) -> Result<(u32, u32), String> {
+    let properties =
+        ImageProperties::<DynamicImage>::read(path, IccMode::Convert, Budget::new(args).as_ref())?;
+
+    match pipeline::run(args, path, "tall", properties.image, properties.strips) {
+        Outcome::Processed(image, _) => Ok(image.dimensions()),
+        _ => Err(String::from("not processed")),
+    }
+}
+
+#[test]
+fn large_image_is_cropped_in_strips() {
+    let path = write_tall_image("strips");
+
+    let bounded = args(&["-c", "-p", "5", "--max-memory", "1"]);
+    let properties = ImageProperties::<DynamicImage>::read(
+        &path,
+        IccMode::Convert,
+        Budget::new(&bounded).as_ref(),
+    )
+    .unwrap();
+    assert!(properties.strips.is_some());
+
+    let unbounded = args(&["-c", "-p", "5"]);
+    assert_eq!(process(&bounded, &path), process(&unbounded, &path));
+    assert_eq!(process(&bounded, &path), Ok((50, 50)));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn large_image_without_crop_is_skipped() {
+    let path = write_tall_image("skipped");
+
+    let message = process(&args(&["-s", "--max-memory", "1"]), &path).unwrap_err();
+    assert!(message.contains("more than --max-memory 1 MB"), "{message}");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Plan for a white RGB image of `width`x`height` within 1 MB
+fn plan((width, height): (u32, u32), options: &[&str]) -> Result<Plan, String> {
+    let mut png = Vec::new();
+    RgbImage::from_pixel(width, height, Rgb([255, 255, 255]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let mut decoder = PngDecoder::new(Cursor::new(png)).unwrap();
+    let header = Header::read(&mut decoder, false).unwrap();
+
+    let options = [options, &["--max-memory", "1"]].concat();
+    Budget::new(&args(&options)).unwrap().plan(&header)
+}
+
+#[test]
+fn upscaling_is_estimated() {
+    assert_eq!(plan((100, 100), &[]), Ok(Plan::Full));
+    // 100x1000 f32 samples of the vertical pass alone are 1.6 MB
+    assert!(plan((100, 100), &["-w", "1000"]).is_err());
+}
+
+#[test]
+fn linear_resize_is_estimated() {
+    assert_eq!(plan((300, 300), &["-w", "100"]), Ok(Plan::Full));
+    // The premultiplied f32 copy of the source is 1.4 MB
+    assert!(plan((300, 300), &["-w", "100", "--linear"]).is_err());
+}
+
+#[test]
+fn edge_detection_is_estimated() {
+    assert_eq!(plan((250, 250), &[]), Ok(Plan::Full));
+    assert!(plan((250, 250), &["-e", "1,10"]).is_err());
+}
+
+#[test]
+fn key_mattes_are_estimated() {
+    // Resized to 10 pixels the image itself and its key mattes are the largest step
+    assert_eq!(plan((370, 370), &["-w", "10"]), Ok(Plan::Full));
+    assert!(plan((370, 370), &["-w", "10", "--key", "0,177,64"]).is_err());
+}
@@ -0,0 +1,77 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use imoyo::args::Args;
+use imoyo::pipeline::{self, Outcome};
+use imoyo::report::{self, ImageReport, ReportFormat};
+use serde_json::{json, Value};
+
+/// Analyzes a dark block at 0..30 x 10..30 on a white 60x40 image
+fn analyze(options: &[&str], source: &str) -> ImageReport {
+    let image = RgbImage::from_fn(60, 40, |x, y| {
+        if x < 30 && (10..30).contains(&y) {
+            Rgb([20, 20, 20])
+        } else {
+            Rgb([255, 255, 255])
+        }
+    });
+    let args = Args::parse(
+        &["imoyo", "--analyze", "json"]
+            .iter()
+            .chain(options)
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>(),
+    );
+
+    match pipeline::run(&args, source, "img", DynamicImage::ImageRgb8(image), None) {
+        Outcome::Analyzed(report) => report,
+        _ => panic!("Image was not analyzed"),
+    }
+}
+
+#[test]
+fn json_report_describes_each_detector() {
+    let reports = [analyze(&["-e", "1,10", "-p", "5"], "shoe.png")];
+    let json: Value = serde_json::from_str(&report::render(ReportFormat::Json, &reports)).unwrap();
+
+    let image = &json[0];
+    assert_eq!(
+        (&image["file"], &image["width"], &image["height"]),
+        (&json!("shoe.png"), &json!(60), &json!(40))
+    );
+    assert_eq!(image["padding"], 5);
+    // Border pixels of the object darken the estimate
+    assert!(image["background"].as_str().unwrap().starts_with("#e7e7e7"));
+
+    let crop = &image["detections"][0];
+    assert_eq!(crop["detector"], "crop");
+    assert_eq!(
+        crop["bounds"],
+        json!({ "min_x": 0, "min_y": 10, "max_x": 29, "max_y": 29 })
+    );
+    assert_eq!(crop["fill_ratio"], 0.25);
+    assert_eq!(crop["touching"], json!(["left"]));
+    assert_eq!(crop["warnings"], json!(["touches_border"]));
+
+    assert_eq!(image["detections"][1]["detector"], "edges");
+}
+
+#[test]
+fn json_report_escapes_file_names() {
+    let file = "shop \"best\"\\shoe\n.png";
+    let reports = [analyze(&[], file)];
+    let json: Value = serde_json::from_str(&report::render(ReportFormat::Json, &reports)).unwrap();
+
+    assert_eq!(json[0]["file"], file);
+}
+
+#[test]
+fn json_report_lists_every_image() {
+    let reports = [
+        analyze(&["--padding", "0,0,15%,0"], "a.png"),
+        analyze(&[], "b.png"),
+    ];
+    let json: Value = serde_json::from_str(&report::render(ReportFormat::Json, &reports)).unwrap();
+
+    assert_eq!(json[0]["padding"], "0,0,15%,0");
+    assert_eq!(json[1]["file"], "b.png");
+    assert_eq!(json.as_array().unwrap().len(), 2);
+}
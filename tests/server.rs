@@ -0,0 +1,115 @@
+use imoyo::args::Args;
+use imoyo::crop::{Length, Padding};
+use imoyo::image_writer::OutputFormat;
+use imoyo::server::{self, OptionValue};
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Pipeline arguments for a request to `imoyo serve <defaults>` with the query `query`
+fn request(defaults: &[&str], query: &str) -> Args {
+    let options = server::query_options(query).unwrap();
+
+    Args::parse(&server::request_args(&strings(defaults), options))
+}
+
+#[test]
+fn option_values() {
+    let flag = |name: &str, value: Option<&str>| {
+        server::option(name, value.map(String::from)).map(|(_, value)| value)
+    };
+
+    assert_eq!(flag("c", None), Ok(OptionValue::Flag(true)));
+    assert_eq!(flag("c", Some("")), Ok(OptionValue::Flag(true)));
+    assert_eq!(flag("c", Some("true")), Ok(OptionValue::Flag(true)));
+    assert_eq!(flag("c", Some("false")), Ok(OptionValue::Flag(false)));
+    assert_eq!(
+        flag("format", Some("png")),
+        Ok(OptionValue::Text(String::from("png")))
+    );
+    assert_eq!(flag("z", None), Err(String::from("Unknown option z")));
+}
+
+#[test]
+fn percent_decoding() {
+    assert_eq!(server::percent_decode("0%2C177%2C64"), "0,177,64");
+    assert_eq!(server::percent_decode("a+b"), "a b");
+    assert_eq!(server::percent_decode("100%"), "100%");
+    assert_eq!(server::percent_decode("%zz"), "%zz");
+    assert_eq!(server::percent_decode("%C3%A9"), "é");
+}
+
+#[test]
+fn multipart_parts() {
+    let content_type = "multipart/form-data; boundary=\"xyz\"";
+    let body = b"--xyz\r\n\
+        Content-Disposition: form-data; name=\"options\"\r\n\r\n\
+        {\"c\": true}\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"image\"; filename=\"in.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        \x89PNG\r\n\x1a\n\r\n\
+        --xyz--\r\n";
+
+    let boundary = server::boundary(content_type).unwrap();
+    let parts = server::multipart(body, &boundary);
+
+    assert_eq!(boundary, "xyz");
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].name, "options");
+    assert!(!parts[0].filename);
+    assert_eq!(parts[0].data, b"{\"c\": true}");
+    assert_eq!(parts[1].name, "image");
+    assert!(parts[1].filename);
+    assert_eq!(parts[1].data, b"\x89PNG\r\n\x1a\n");
+
+    assert_eq!(server::boundary("application/json"), None);
+}
+
+#[test]
+fn json_options_are_request_options() {
+    let options = server::json_options(br#"{"c": false, "w": 500}"#).unwrap();
+
+    assert_eq!(
+        options,
+        vec![
+            (String::from("c"), OptionValue::Flag(false)),
+            (String::from("w"), OptionValue::Text(String::from("500"))),
+        ]
+    );
+    assert!(server::json_options(b"[1]").is_err());
+}
+
+#[test]
+fn request_options_override_defaults() {
+    let args = request(&["--format", "png", "-cp", "10"], "format=jpeg&p=20");
+
+    assert_eq!(args.format, OutputFormat::Jpeg);
+    assert!(args.crop);
+    assert_eq!(args.padding, Padding::uniform(Length::Pixels(20)));
+}
+
+#[test]
+fn request_can_remove_default_flags() {
+    let args = request(
+        &["-cs", "--padding", "5"],
+        "c=false&crop=false&padding=false",
+    );
+
+    assert!(!args.crop);
+    assert!(args.square);
+    assert_eq!(args.padding, Padding::default());
+
+    assert!(!request(&["--crop"], "c=false").crop);
+}
+
+#[test]
+fn later_request_options_win() {
+    let options = server::query_options("format=png&format=webp").unwrap();
+
+    assert_eq!(
+        Args::parse(&server::request_args(&[], options)).format,
+        OutputFormat::Webp
+    );
+}
@@ -0,0 +1,72 @@
+use image::{Rgba, RgbaImage};
+use imoyo::background::Background;
+use imoyo::crop::{Crop, Padding};
+use imoyo::shadow::{self, ShadowMode};
+
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+fn crop(shadow: Option<ShadowMode>) -> Crop {
+    Crop::new(Padding::default(), Background::white(), None).with_shadow(shadow)
+}
+
+/// Grey product at x 20..80 with a dark logo in its middle
+fn grey_object() -> RgbaImage {
+    RgbaImage::from_fn(100, 80, |x, y| {
+        if (45..55).contains(&x) && (35..45).contains(&y) {
+            Rgba([30, 30, 30, 255])
+        } else if (20..80).contains(&x) && (10..70).contains(&y) {
+            Rgba([150, 150, 150, 255])
+        } else {
+            WHITE
+        }
+    })
+}
+
+/// Dark object at x 10..40 casting a shadow to the right that fades out by x 80
+fn object_with_shadow() -> RgbaImage {
+    RgbaImage::from_fn(100, 60, |x, y| {
+        if !(10..50).contains(&y) {
+            WHITE
+        } else if (10..40).contains(&x) {
+            Rgba([40, 30, 20, 255])
+        } else if (40..80).contains(&x) {
+            let value = 200 + (x - 40) * 55 / 40;
+            Rgba([value as u8, value as u8, value as u8, 255])
+        } else {
+            WHITE
+        }
+    })
+}
+
+#[test]
+fn grey_object_is_not_a_shadow() {
+    let image = grey_object();
+
+    assert!(!shadow::detect(&image, &Background::white()).contains(&true));
+
+    let object = crop(Some(ShadowMode::Exclude)).detect(&image).unwrap();
+    assert_eq!((object.min_x, object.max_x), (20, 79));
+    assert_eq!((object.min_y, object.max_y), (10, 69));
+}
+
+#[test]
+fn soft_shadow_is_excluded() {
+    let image = object_with_shadow();
+
+    let included = crop(None).detect(&image).unwrap();
+    assert!(included.max_x > 70);
+
+    let excluded = crop(Some(ShadowMode::Exclude)).detect(&image).unwrap();
+    assert_eq!((excluded.min_x, excluded.max_x), (10, 39));
+    assert_eq!((excluded.min_y, excluded.max_y), (10, 49));
+}
+
+#[test]
+fn removed_shadow_keeps_the_object() {
+    let mut image = object_with_shadow();
+    crop(Some(ShadowMode::Remove)).remove_shadow(&mut image);
+
+    assert_eq!(image.get_pixel(39, 30), &Rgba([40, 30, 20, 255]));
+    assert_eq!(image.get_pixel(40, 30)[3], 0);
+    assert_eq!(image.get_pixel(70, 30)[3], 0);
+}